@@ -1,24 +1,227 @@
 use std::fmt;
+use std::ops::RangeInclusive;
 
-#[derive(Debug, PartialEq, Clone)]
+use crate::span::Spanned;
+
+/// Variable like `A` or `TOTAL2`, or a string variable like `NAME$`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Variable {
-    identifier: u8,
+    /// Upper-case name, without the `$` of a string variable.
+    name: String,
+    is_string: bool,
 }
 
 impl Variable {
-    pub fn new(identifier: u8) -> Self {
-        debug_assert!(identifier.is_ascii_uppercase());
-        Self { identifier }
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        debug_assert_eq!(name, name.to_ascii_uppercase());
+        Self {
+            name,
+            is_string: false,
+        }
     }
 
-    pub fn identifier(&self) -> u8 {
-        self.identifier
+    /// Creates a variable holding a string, like `A$`.
+    pub fn string(name: impl Into<String>) -> Self {
+        let name = name.into();
+        debug_assert_eq!(name, name.to_ascii_uppercase());
+        Self {
+            name,
+            is_string: true,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_string(&self) -> bool {
+        self.is_string
     }
 }
 
 impl fmt::Display for Variable {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.identifier as char)
+        write!(f, "{}", self.name)?;
+
+        if self.is_string {
+            write!(f, "$")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Element of an array like `A(I, J)`.
+///
+/// In `DIM`, the indices are the upper bounds of the array being declared.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ArrayElement {
+    variable: Variable,
+    indices: Vec<Expression>,
+}
+
+impl ArrayElement {
+    pub fn new(variable: Variable, indices: Vec<Expression>) -> Self {
+        debug_assert!(!indices.is_empty());
+        Self { variable, indices }
+    }
+
+    pub fn variable(&self) -> &Variable {
+        &self.variable
+    }
+
+    pub fn indices(&self) -> &[Expression] {
+        &self.indices
+    }
+}
+
+impl fmt::Display for ArrayElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.variable)?;
+        write_arguments(f, &self.indices)
+    }
+}
+
+/// Built-in function.
+///
+/// String functions work on bytes, so `LEN` counts the bytes of a string.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Function {
+    Abs,
+    Sgn,
+    Int,
+    Sqr,
+    Sin,
+    Cos,
+    Atn,
+    Exp,
+    Log,
+    Len,
+    Left,
+    Right,
+    Mid,
+    Chr,
+    Asc,
+    Str,
+    Val,
+    Instr,
+}
+
+impl Function {
+    const ALL: [Function; 18] = [
+        Function::Abs,
+        Function::Sgn,
+        Function::Int,
+        Function::Sqr,
+        Function::Sin,
+        Function::Cos,
+        Function::Atn,
+        Function::Exp,
+        Function::Log,
+        Function::Len,
+        Function::Left,
+        Function::Right,
+        Function::Mid,
+        Function::Chr,
+        Function::Asc,
+        Function::Str,
+        Function::Val,
+        Function::Instr,
+    ];
+
+    /// Looks up the function called `name`, given in upper case and with the `$` of functions
+    /// giving a string, like `LEFT$`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|function| function.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Function::Abs => "ABS",
+            Function::Sgn => "SGN",
+            Function::Int => "INT",
+            Function::Sqr => "SQR",
+            Function::Sin => "SIN",
+            Function::Cos => "COS",
+            Function::Atn => "ATN",
+            Function::Exp => "EXP",
+            Function::Log => "LOG",
+            Function::Len => "LEN",
+            Function::Left => "LEFT$",
+            Function::Right => "RIGHT$",
+            Function::Mid => "MID$",
+            Function::Chr => "CHR$",
+            Function::Asc => "ASC",
+            Function::Str => "STR$",
+            Function::Val => "VAL",
+            Function::Instr => "INSTR",
+        }
+    }
+
+    /// Returns the numbers of arguments the function can be called with.
+    pub fn arity(&self) -> RangeInclusive<usize> {
+        match self {
+            Function::Left | Function::Right => 2..=2,
+            // `MID$(text, start[, length])` and `INSTR([start, ]text, pattern)`
+            Function::Mid | Function::Instr => 2..=3,
+            _ => 1..=1,
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Call of a built-in function like `SQR(X)`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FunctionCall {
+    function: Function,
+    arguments: Vec<Expression>,
+}
+
+impl FunctionCall {
+    pub fn new(function: Function, arguments: Vec<Expression>) -> Self {
+        Self {
+            function,
+            arguments,
+        }
+    }
+
+    pub fn function(&self) -> Function {
+        self.function
+    }
+
+    pub fn arguments(&self) -> &[Expression] {
+        &self.arguments
+    }
+}
+
+impl fmt::Display for FunctionCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.function)?;
+        write_arguments(f, &self.arguments)
+    }
+}
+
+/// Where `LET` stores its value.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Target {
+    Variable(Variable),
+    ArrayElement(ArrayElement),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Variable(variable) => write!(f, "{}", variable),
+            Target::ArrayElement(element) => write!(f, "{}", element),
+        }
     }
 }
 
@@ -43,6 +246,34 @@ impl fmt::Display for NumberLiteral {
     }
 }
 
+/// Number with a fraction or an exponent, like `1.5` or `2E-3`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FloatLiteral {
+    value: f64,
+}
+
+impl FloatLiteral {
+    pub fn new(value: f64) -> Self {
+        Self { value }
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+}
+
+impl fmt::Display for FloatLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // unlike `Display`, `Debug` keeps the fraction of whole numbers, like `2.0`, which is
+        // only needed for those that would otherwise be read as integers
+        let text = format!("{:?}", self.value).to_uppercase();
+        match text.strip_suffix(".0") {
+            Some(whole) if whole.parse::<i16>().is_err() => write!(f, "{}", whole),
+            _ => write!(f, "{}", text),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct StringLiteral {
     value: Vec<u8>,
@@ -60,59 +291,85 @@ impl StringLiteral {
 
 impl fmt::Display for StringLiteral {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", String::from_utf8_lossy(&self.value))
+        write!(f, "\"{}\"", String::from_utf8_lossy(&self.value))
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Factor {
     Variable(Variable),
+    ArrayElement(ArrayElement),
+    FunctionCall(FunctionCall),
     NumberLiteral(NumberLiteral),
+    FloatLiteral(FloatLiteral),
+    StringLiteral(StringLiteral),
     Expression(Box<Expression>),
+    /// `base ^ exponent`, where `exponent` may itself be a power.
+    Power {
+        base: Box<Factor>,
+        exponent: Box<Factor>,
+    },
+    /// Signed exponent of a power, like `-1` in `2 ^ -1`.
+    Unary {
+        operator: AdditiveOperator,
+        factor: Box<Factor>,
+    },
 }
 
 impl fmt::Display for Factor {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Factor::Variable(variable) => write!(f, "{}", variable),
+            Factor::ArrayElement(element) => write!(f, "{}", element),
+            Factor::FunctionCall(call) => write!(f, "{}", call),
+            Factor::FloatLiteral(float_literal) => write!(f, "{}", float_literal),
+            Factor::StringLiteral(string_literal) => write!(f, "{}", string_literal),
             Factor::NumberLiteral(number_literal) => write!(f, "{}", number_literal),
             Factor::Expression(expression) => write!(f, "({})", expression),
+            Factor::Power { base, exponent } => write!(f, "{} ^ {}", base, exponent),
+            Factor::Unary { operator, factor } => write!(f, "{}{}", operator, factor),
         }
     }
 }
 
+/// Factors combined by multiplicative operators, nested by their precedence.
+///
+/// Factors keep the span of their source text and operations the span of their operator.
 #[derive(Debug, PartialEq, Clone)]
-pub struct Term {
-    factor: Factor,
-    operations: Vec<(MultiplicativeOperator, Factor)>,
+pub enum Term {
+    Factor(Spanned<Factor>),
+    Operation {
+        left: Box<Term>,
+        operator: Spanned<MultiplicativeOperator>,
+        right: Box<Term>,
+    },
 }
 
 impl Term {
-    pub fn new(factor: Factor, next: Vec<(MultiplicativeOperator, Factor)>) -> Self {
-        Self {
-            factor,
-            operations: next,
-        }
-    }
-
-    pub fn factor(&self) -> &Factor {
-        &self.factor
-    }
-
-    pub fn operations(&self) -> &[(MultiplicativeOperator, Factor)] {
-        &self.operations
+    /// Creates the term applying `operations` to `factor` from left to right.
+    pub fn new(factor: Factor, operations: Vec<(MultiplicativeOperator, Factor)>) -> Self {
+        operations
+            .into_iter()
+            .fold(Term::Factor(factor.into()), |left, (operator, right)| {
+                Term::Operation {
+                    left: Box::new(left),
+                    operator: operator.into(),
+                    right: Box::new(Term::Factor(right.into())),
+                }
+            })
     }
 }
 
 impl fmt::Display for Term {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.factor)?;
-
-        for operation in &self.operations {
-            write!(f, " {} {}", operation.0, operation.1)?;
+        match self {
+            Term::Factor(factor) => write!(f, "{}", factor.node()),
+            Term::Operation {
+                left,
+                operator,
+                right,
+            } => write!(f, "{} {} {}", left, operator.node(), right),
         }
-
-        Ok(())
     }
 }
 
@@ -120,6 +377,20 @@ impl fmt::Display for Term {
 pub enum MultiplicativeOperator {
     Multiplication,
     Division,
+    IntegerDivision,
+    Modulo,
+}
+
+impl MultiplicativeOperator {
+    /// Returns how tightly the operator binds, higher for `*` and `/` than for `\` and higher
+    /// for `\` than for `MOD`.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            MultiplicativeOperator::Multiplication | MultiplicativeOperator::Division => 3,
+            MultiplicativeOperator::IntegerDivision => 2,
+            MultiplicativeOperator::Modulo => 1,
+        }
+    }
 }
 
 impl fmt::Display for MultiplicativeOperator {
@@ -127,22 +398,25 @@ impl fmt::Display for MultiplicativeOperator {
         match self {
             MultiplicativeOperator::Multiplication => write!(f, "*"),
             MultiplicativeOperator::Division => write!(f, "/"),
+            MultiplicativeOperator::IntegerDivision => write!(f, "\\"),
+            MultiplicativeOperator::Modulo => write!(f, "MOD"),
         }
     }
 }
 
+/// Terms combined by `+` and `-`, whose operators keep their span.
 #[derive(Debug, PartialEq, Clone)]
-pub struct Expression {
+pub struct ArithmeticExpression {
     unary_operator: Option<AdditiveOperator>,
     term: Term,
-    others: Vec<(AdditiveOperator, Term)>,
+    others: Vec<(Spanned<AdditiveOperator>, Term)>,
 }
 
-impl Expression {
+impl ArithmeticExpression {
     pub fn new(
         unary_operator: Option<AdditiveOperator>,
         term: Term,
-        others: Vec<(AdditiveOperator, Term)>,
+        others: Vec<(Spanned<AdditiveOperator>, Term)>,
     ) -> Self {
         Self {
             unary_operator,
@@ -159,12 +433,12 @@ impl Expression {
         &self.term
     }
 
-    pub fn others(&self) -> &[(AdditiveOperator, Term)] {
+    pub fn others(&self) -> &[(Spanned<AdditiveOperator>, Term)] {
         &self.others
     }
 }
 
-impl fmt::Display for Expression {
+impl fmt::Display for ArithmeticExpression {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if let Some(operator) = &self.unary_operator {
             write!(f, "{}", operator)?;
@@ -173,7 +447,7 @@ impl fmt::Display for Expression {
         write!(f, "{}", self.term)?;
 
         for (operator, term) in &self.others {
-            write!(f, " {} {}", operator, term)?;
+            write!(f, " {} {}", operator.node(), term)?;
         }
 
         Ok(())
@@ -195,17 +469,65 @@ impl fmt::Display for AdditiveOperator {
     }
 }
 
+/// Comparisons and the boolean operators evaluate to -1 for true and 0 for false.
 #[derive(Debug, PartialEq, Clone)]
-pub enum ExpressionListElement {
-    StringLiteral(StringLiteral),
-    Expression(Expression),
+pub enum Expression {
+    Arithmetic(ArithmeticExpression),
+    Comparison {
+        left: Box<ArithmeticExpression>,
+        operator: RelationalOperator,
+        right: Box<ArithmeticExpression>,
+    },
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
 }
 
-impl fmt::Display for ExpressionListElement {
+impl fmt::Display for Expression {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            ExpressionListElement::StringLiteral(string_literal) => write!(f, "{}", string_literal),
-            ExpressionListElement::Expression(expression) => write!(f, "{}", expression),
+            Expression::Arithmetic(expression) => write!(f, "{}", expression),
+            Expression::Comparison {
+                left,
+                operator,
+                right,
+            } => write!(f, "{} {} {}", left, operator, right),
+            Expression::Not(expression) => write!(f, "NOT {}", expression),
+            Expression::And(left, right) => write!(f, "{} AND {}", left, right),
+            Expression::Or(left, right) => write!(f, "{} OR {}", left, right),
+        }
+    }
+}
+
+/// What `IF` runs after `THEN` or `ELSE`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Branch {
+    /// Line number shorthand for `GOTO`, like `THEN 100`
+    LineNumber(u16),
+    Statements(Vec<Spanned<Statement>>),
+}
+
+impl fmt::Display for Branch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Branch::LineNumber(line_number) => write!(f, "{}", line_number),
+            Branch::Statements(statements) => write_statements(f, statements),
+        }
+    }
+}
+
+/// The optional `WHILE` or `UNTIL` clause of `DO` and `LOOP`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum LoopCondition {
+    While(Expression),
+    Until(Expression),
+}
+
+impl fmt::Display for LoopCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoopCondition::While(condition) => write!(f, "WHILE {}", condition),
+            LoopCondition::Until(condition) => write!(f, "UNTIL {}", condition),
         }
     }
 }
@@ -213,13 +535,12 @@ impl fmt::Display for ExpressionListElement {
 #[derive(Debug, PartialEq, Clone)]
 pub enum Statement {
     Print {
-        expression_list: Vec<ExpressionListElement>,
+        expression_list: Vec<Expression>,
     },
     If {
-        left: Expression,
-        operator: RelationalOperator,
-        right: Expression,
-        then: Box<Statement>,
+        condition: Expression,
+        then: Branch,
+        otherwise: Option<Branch>,
     },
     Goto {
         expression: Expression,
@@ -228,9 +549,13 @@ pub enum Statement {
         variable_list: Vec<Variable>,
     },
     Let {
-        variable: Variable,
+        target: Target,
         expression: Expression,
     },
+    /// `DIM` of one or more arrays
+    Dim {
+        arrays: Vec<ArrayElement>,
+    },
     GoSub {
         expression: Expression,
     },
@@ -239,6 +564,34 @@ pub enum Statement {
     List,
     Run,
     End,
+    Bye,
+    For {
+        variable: Variable,
+        from: Expression,
+        to: Expression,
+        step: Option<Expression>,
+    },
+    Next {
+        variable: Option<Variable>,
+    },
+    While {
+        condition: Expression,
+    },
+    Wend,
+    Do {
+        condition: Option<LoopCondition>,
+    },
+    Loop {
+        condition: Option<LoopCondition>,
+    },
+    /// `REM` comment, with the text following the keyword
+    Remark {
+        text: Vec<u8>,
+    },
+    /// `'` comment, with the text following the apostrophe
+    Comment {
+        text: Vec<u8>,
+    },
 }
 
 impl fmt::Display for Statement {
@@ -258,11 +611,18 @@ impl fmt::Display for Statement {
                 Ok(())
             }
             Statement::If {
-                left,
-                operator,
-                right,
+                condition,
                 then,
-            } => write!(f, "IF {} {} {} THEN {}", left, operator, right, then),
+                otherwise,
+            } => {
+                write!(f, "IF {} THEN {}", condition, then)?;
+
+                if let Some(otherwise) = otherwise {
+                    write!(f, " ELSE {}", otherwise)?;
+                }
+
+                Ok(())
+            }
             Statement::Goto { expression } => write!(f, "GOTO {}", expression),
             Statement::Input { variable_list } => {
                 write!(f, "INPUT ")?;
@@ -277,18 +637,89 @@ impl fmt::Display for Statement {
 
                 Ok(())
             }
-            Statement::Let {
-                variable,
-                expression,
-            } => write!(f, "LET {} = {}", variable, expression),
+            Statement::Let { target, expression } => write!(f, "LET {} = {}", target, expression),
+            Statement::Dim { arrays } => {
+                write!(f, "DIM ")?;
+
+                for (i, array) in arrays.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{}", array)?;
+                }
+
+                Ok(())
+            }
             Statement::GoSub { expression } => write!(f, "GOSUB {}", expression),
             Statement::Return => write!(f, "RETURN"),
             Statement::Clear => write!(f, "CLEAR"),
             Statement::List => write!(f, "LIST"),
             Statement::Run => write!(f, "RUN"),
             Statement::End => write!(f, "END"),
+            Statement::Bye => write!(f, "BYE"),
+            Statement::For {
+                variable,
+                from,
+                to,
+                step,
+            } => {
+                write!(f, "FOR {} = {} TO {}", variable, from, to)?;
+
+                if let Some(step) = step {
+                    write!(f, " STEP {}", step)?;
+                }
+
+                Ok(())
+            }
+            Statement::Next { variable } => match variable {
+                Some(variable) => write!(f, "NEXT {}", variable),
+                None => write!(f, "NEXT"),
+            },
+            Statement::While { condition } => write!(f, "WHILE {}", condition),
+            Statement::Wend => write!(f, "WEND"),
+            Statement::Do { condition } => match condition {
+                Some(condition) => write!(f, "DO {}", condition),
+                None => write!(f, "DO"),
+            },
+            Statement::Loop { condition } => match condition {
+                Some(condition) => write!(f, "LOOP {}", condition),
+                None => write!(f, "LOOP"),
+            },
+            Statement::Remark { text } => write!(f, "REM{}", String::from_utf8_lossy(text)),
+            Statement::Comment { text } => write!(f, "'{}", String::from_utf8_lossy(text)),
+        }
+    }
+}
+
+/// Writes a parenthesized list like `(1, 2)`.
+fn write_arguments(f: &mut fmt::Formatter, arguments: &[Expression]) -> fmt::Result {
+    write!(f, "(")?;
+
+    for (i, argument) in arguments.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+
+        write!(f, "{}", argument)?;
+    }
+
+    write!(f, ")")
+}
+
+fn write_statements(f: &mut fmt::Formatter, statements: &[Spanned<Statement>]) -> fmt::Result {
+    for (i, statement) in statements.iter().map(Spanned::node).enumerate() {
+        if i > 0 {
+            match statement {
+                Statement::Comment { .. } => write!(f, " ")?,
+                _ => write!(f, ": ")?,
+            }
         }
+
+        write!(f, "{}", statement)?;
     }
+
+    Ok(())
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -314,23 +745,53 @@ impl fmt::Display for RelationalOperator {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// The largest line number a stored line can have.
+pub const MAX_LINE_NUMBER: u16 = 32767;
+
+/// A line of statements.
+///
+/// Like spans, the source is not compared, so that lines read from differently formatted source
+/// are equal.
+#[derive(Debug, Clone)]
 pub struct Line {
-    number: Option<u8>,
-    statement: Statement,
+    number: Option<u16>,
+    statements: Vec<Spanned<Statement>>,
+    source: Option<String>,
 }
 
 impl Line {
-    pub fn new(number: Option<u8>, statement: Statement) -> Self {
-        Self { number, statement }
+    pub fn new(number: Option<u16>, statements: Vec<Spanned<Statement>>) -> Self {
+        debug_assert!(!statements.is_empty());
+        Self {
+            number,
+            statements,
+            source: None,
+        }
+    }
+
+    /// Keeps the source text the line was parsed from, which the spans of its statements refer
+    /// to.
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = Some(source.to_string());
+        self
     }
 
-    pub fn number(&self) -> Option<u8> {
+    pub fn number(&self) -> Option<u16> {
         self.number
     }
 
-    pub fn statement(&self) -> &Statement {
-        &self.statement
+    pub fn statements(&self) -> &[Spanned<Statement>] {
+        &self.statements
+    }
+
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+}
+
+impl PartialEq for Line {
+    fn eq(&self, other: &Self) -> bool {
+        self.number == other.number && self.statements == other.statements
     }
 }
 
@@ -340,6 +801,6 @@ impl fmt::Display for Line {
             write!(f, "{} ", number)?;
         }
 
-        write!(f, "{}", self.statement)
+        write_statements(f, &self.statements)
     }
 }
@@ -0,0 +1,78 @@
+use std::collections::VecDeque;
+use std::io::{self, stdin, stdout, Write};
+
+/// Text I/O used by `PRINT` and `INPUT`.
+pub trait Console {
+    fn write(&mut self, text: &str) -> io::Result<()>;
+
+    /// Reads a line without its line terminator.
+    ///
+    /// Returns an [`io::ErrorKind::UnexpectedEof`] error when there is no more input.
+    fn read_line(&mut self) -> io::Result<String>;
+}
+
+/// Console backed by the standard input and output streams.
+#[derive(Debug, Default)]
+pub struct StdConsole;
+
+impl Console for StdConsole {
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        let mut stdout = stdout().lock();
+        stdout.write_all(text.as_bytes())?;
+        stdout.flush()
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut buffer = String::new();
+        if stdin().read_line(&mut buffer)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let length = buffer.trim_end_matches(['\r', '\n']).len();
+        buffer.truncate(length);
+
+        Ok(buffer)
+    }
+}
+
+/// In-memory console that replays scripted input and records all output.
+#[derive(Debug, Default)]
+pub struct BufferConsole {
+    input: VecDeque<String>,
+    output: String,
+}
+
+impl BufferConsole {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a console whose `INPUT` statements will read `lines` in order.
+    pub fn with_input<I, S>(lines: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            input: lines.into_iter().map(Into::into).collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+}
+
+impl Console for BufferConsole {
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        self.output.push_str(text);
+        Ok(())
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        self.input
+            .pop_front()
+            .ok_or_else(|| io::ErrorKind::UnexpectedEof.into())
+    }
+}
@@ -1,137 +1,557 @@
-use std::io::stdin;
-use std::process::exit;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
 
 use crate::ast::{
-    AdditiveOperator, Expression, ExpressionListElement, Factor, Line, MultiplicativeOperator,
-    RelationalOperator, Statement, Term,
+    AdditiveOperator, ArithmeticExpression, ArrayElement, Expression, Factor, Function, Line,
+    LoopCondition, MultiplicativeOperator, RelationalOperator, Statement, Target, Term, Variable,
+    MAX_LINE_NUMBER,
 };
+use crate::console::{Console, StdConsole};
+use crate::program::{Instruction, Position, Program};
+use crate::span::Span;
 
-const STORAGE_SIZE: usize = 256;
-const NUM_VARIABLES: usize = 26;
+/// Maximum number of elements of a single array.
+const MAX_ARRAY_SIZE: usize = 1 << 16;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Error {
     LineNumberOutOfRange,
-    UnknownLineNumber,
-    WrongUserInput,
-    CannotParseNumber,
+    MissingLineNumber,
+    UnknownLineNumber(u16),
+    NextWithoutFor,
+    ForWithoutNext,
+    WendWithoutWhile,
+    WhileWithoutWend,
+    LoopWithoutDo,
+    DoWithoutLoop,
+    DivisionByZero,
+    Overflow,
+    TypeMismatch,
+    /// The argument of `function` is outside of its domain, like in `SQR(-1)`.
+    IllegalFunctionCall(Function),
+    UndimensionedArray(String),
+    SubscriptOutOfRange,
+    WrongNumberOfSubscripts {
+        expected: usize,
+        found: usize,
+    },
+    IllegalArraySize,
+    Io(io::ErrorKind),
 }
 
-pub struct Evaluator {
-    storage: [Option<Line>; STORAGE_SIZE],
-    stack: Vec<usize>,
-    program_counter: usize,
-    variables: [i16; NUM_VARIABLES],
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::LineNumberOutOfRange => {
+                write!(f, "line numbers must be between 1 and {}", MAX_LINE_NUMBER)
+            }
+            Error::MissingLineNumber => write!(f, "program lines must start with a line number"),
+            Error::UnknownLineNumber(line_number) => {
+                write!(f, "there is no line {}", line_number)
+            }
+            Error::NextWithoutFor => write!(f, "NEXT has no matching FOR"),
+            Error::ForWithoutNext => write!(f, "FOR has no matching NEXT"),
+            Error::WendWithoutWhile => write!(f, "WEND has no matching WHILE"),
+            Error::WhileWithoutWend => write!(f, "WHILE has no matching WEND"),
+            Error::LoopWithoutDo => write!(f, "LOOP has no matching DO"),
+            Error::DoWithoutLoop => write!(f, "DO has no matching LOOP"),
+            Error::DivisionByZero => write!(f, "division by zero"),
+            Error::Overflow => write!(
+                f,
+                "result is out of the range {} to {} of integers or too large for a float",
+                i16::MIN,
+                i16::MAX
+            ),
+            Error::TypeMismatch => write!(f, "strings and numbers cannot be mixed"),
+            Error::IllegalFunctionCall(function) => {
+                write!(f, "argument of {} is out of its domain", function)
+            }
+            Error::UndimensionedArray(name) => write!(f, "array {} is used before DIM", name),
+            Error::SubscriptOutOfRange => write!(f, "array index is out of bounds"),
+            Error::WrongNumberOfSubscripts { expected, found } => write!(
+                f,
+                "array has {} dimensions but {} indices were given",
+                expected, found
+            ),
+            Error::IllegalArraySize => write!(
+                f,
+                "array bounds must not be negative and the array may hold at most {} elements",
+                MAX_ARRAY_SIZE
+            ),
+            Error::Io(kind) => write!(f, "console failed: {}", kind),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error.kind())
+    }
+}
+
+/// How a line or a program run came to an end.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Outcome {
+    /// Execution ran past its last statement.
+    Finished,
+    /// Execution was stopped by `END`.
+    Stopped,
+    /// `BYE` was executed, the host should quit.
+    Exit,
+}
+
+/// A value computed by an expression or held by a variable.
+///
+/// Numeric variables hold integers or floats. Arithmetic on two integers gives an integer if
+/// the result fits in one, so `200 * 200` is the float 40000, and arithmetic involving a float
+/// gives a float. `/` and powers with a negative exponent always give a float, so `7 / 2` is
+/// 3.5, while `7 \ 2` is 3.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Integer(i16),
+    Float(f64),
+    String(Vec<u8>),
+}
+
+impl Value {
+    /// Returns the value variables start with, 0 or the empty string.
+    fn initial(is_string: bool) -> Self {
+        if is_string {
+            Value::String(Vec::new())
+        } else {
+            Value::Integer(0)
+        }
+    }
+
+    /// Converts a number to an integer, dropping the fraction of a float.
+    fn to_integer(&self) -> Result<i16, Error> {
+        match self {
+            Value::Integer(value) => Ok(*value),
+            Value::Float(value) => {
+                let value = value.trunc();
+                if (f64::from(i16::MIN)..=f64::from(i16::MAX)).contains(&value) {
+                    Ok(value as i16)
+                } else {
+                    Err(Error::Overflow)
+                }
+            }
+            Value::String(_) => Err(Error::TypeMismatch),
+        }
+    }
+
+    fn as_bytes(&self) -> Result<&[u8], Error> {
+        match self {
+            Value::String(value) => Ok(value),
+            Value::Integer(_) | Value::Float(_) => Err(Error::TypeMismatch),
+        }
+    }
+
+    fn to_float(&self) -> Result<f64, Error> {
+        match self {
+            Value::Integer(value) => Ok(f64::from(*value)),
+            Value::Float(value) => Ok(*value),
+            Value::String(_) => Err(Error::TypeMismatch),
+        }
+    }
+
+    /// Applies an arithmetic operator, using `integer` if both operands are integers and its
+    /// result fits in an integer, and `float` otherwise.
+    fn calculate(
+        &self,
+        other: &Value,
+        integer: fn(i16, i16) -> Option<i16>,
+        float: fn(f64, f64) -> f64,
+    ) -> Result<Value, Error> {
+        if let (Value::Integer(left), Value::Integer(right)) = (self, other) {
+            if let Some(value) = integer(*left, *right) {
+                return Ok(Value::Integer(value));
+            }
+        }
+
+        Self::float(float(self.to_float()?, other.to_float()?))
+    }
+
+    /// Wraps the result of a float calculation, which overflowed if it is not finite.
+    fn float(value: f64) -> Result<Value, Error> {
+        if !value.is_finite() {
+            Err(Error::Overflow)?;
+        }
+
+        Ok(Value::Float(value))
+    }
+
+    /// Compares two numbers or two strings, which are compared byte by byte.
+    ///
+    /// `-0.0` equals `0`, as the sign of a float zero is not a difference in value.
+    fn compare(&self, other: &Value) -> Result<Ordering, Error> {
+        match (self, other) {
+            (Value::Integer(left), Value::Integer(right)) => Ok(left.cmp(right)),
+            (Value::String(left), Value::String(right)) => Ok(left.cmp(right)),
+            _ => Ok(self
+                .to_float()?
+                .partial_cmp(&other.to_float()?)
+                .expect("numbers are finite")),
+        }
+    }
+}
+
+/// Prints integers as they are and floats with up to 9 significant digits, switching to
+/// exponent notation like `1.5E-03` for very small or large ones.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{}", format_float(*value)),
+            Value::String(value) => write!(f, "{}", String::from_utf8_lossy(value)),
+        }
+    }
+}
+
+/// Formats a float the way BASIC prints it, like `.5`, `-12.25` or `1E+10`.
+fn format_float(value: f64) -> String {
+    const SIGNIFICANT_DIGITS: usize = 9;
+
+    if value == 0.0 {
+        return "0".to_string();
+    }
+
+    // rounds to the significant digits, like `1.50000000e-3`
+    let scientific = format!("{:.*e}", SIGNIFICANT_DIGITS - 1, value);
+    let (mantissa, exponent) = scientific.split_once('e').expect("float has an exponent");
+    let exponent: i32 = exponent.parse().expect("exponent is a number");
+    let mantissa = mantissa.trim_end_matches('0').trim_end_matches('.');
+
+    if !(-2..SIGNIFICANT_DIGITS as i32).contains(&exponent) {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        return format!("{}E{}{:02}", mantissa, sign, exponent.abs());
+    }
+
+    let rounded: f64 = format!("{}e{}", mantissa, exponent)
+        .parse()
+        .expect("mantissa and exponent form a float");
+    let decimals = (SIGNIFICANT_DIGITS as i32 - 1 - exponent).max(0) as usize;
+    let text = format!("{:.*}", decimals, rounded);
+    let text = match text.contains('.') {
+        true => text.trim_end_matches('0').trim_end_matches('.'),
+        false => &text,
+    };
+
+    // BASIC leaves out the zero before the decimal point
+    match text.strip_prefix("0.") {
+        Some(fraction) => format!(".{}", fraction),
+        None => match text.strip_prefix("-0.") {
+            Some(fraction) => format!("-.{}", fraction),
+            None => text.to_string(),
+        },
+    }
+}
+
+/// An active `FOR` loop.
+#[derive(Debug, Clone)]
+struct ForLoop {
+    variable: Variable,
+    limit: Value,
+    step: Value,
+    /// Program counter of the first statement of the loop body.
+    body: Option<Position>,
+}
+
+impl ForLoop {
+    fn is_running(&self, value: &Value) -> Result<bool, Error> {
+        let ordering = value.compare(&self.limit)?;
+        if self.step.compare(&Value::Integer(0))?.is_lt() {
+            Ok(ordering.is_ge())
+        } else {
+            Ok(ordering.is_le())
+        }
+    }
+}
+
+/// An entry of the loop-control stack.
+///
+/// `WHILE` and `DO` loops remember the statement they started on, which is run again on every
+/// iteration to evaluate the loop condition.
+#[derive(Debug, Clone)]
+enum ActiveLoop {
+    For(ForLoop),
+    While { start: Option<Position> },
+    Do { start: Option<Position> },
+}
+
+/// An array declared by `DIM`, indexed from 0 up to and including each bound.
+#[derive(Debug, Clone)]
+struct Array {
+    bounds: Vec<usize>,
+    values: Vec<Value>,
+}
+
+impl Array {
+    fn new(bounds: Vec<usize>, is_string: bool) -> Result<Self, Error> {
+        let size = bounds
+            .iter()
+            .try_fold(1usize, |size, bound| size.checked_mul(bound + 1))
+            .filter(|size| *size <= MAX_ARRAY_SIZE)
+            .ok_or(Error::IllegalArraySize)?;
+
+        Ok(Self {
+            bounds,
+            values: vec![Value::initial(is_string); size],
+        })
+    }
+
+    /// Returns the offset into `values` of the element at `indices`, in row-major order.
+    fn offset(&self, indices: &[i16]) -> Result<usize, Error> {
+        if indices.len() != self.bounds.len() {
+            Err(Error::WrongNumberOfSubscripts {
+                expected: self.bounds.len(),
+                found: indices.len(),
+            })?;
+        }
+
+        let mut offset = 0;
+        for (index, bound) in indices.iter().zip(&self.bounds) {
+            let index = usize::try_from(*index)
+                .ok()
+                .filter(|index| index <= bound)
+                .ok_or(Error::SubscriptOutOfRange)?;
+            offset = offset * (bound + 1) + index;
+        }
+
+        Ok(offset)
+    }
+}
+
+pub struct Evaluator<C: Console = StdConsole> {
+    program: Program,
+    stack: Vec<Option<Position>>,
+    loops: Vec<ActiveLoop>,
+    /// The statement to run next is the first one at or after this position.
+    program_counter: Option<Position>,
+    current_position: Option<Position>,
+    /// Span of the operation or factor that raised the last error, if it was raised by one.
+    error_span: Option<Span>,
+    /// Variables that were assigned, the others hold 0 or the empty string.
+    variables: HashMap<Variable, Value>,
+    /// Arrays live in a namespace of their own, so `A` and `A(1)` are different values.
+    arrays: HashMap<Variable, Array>,
+    console: C,
 }
 
 impl Evaluator {
     pub fn new() -> Self {
+        Self::with_console(StdConsole)
+    }
+}
+
+impl<C: Console> Evaluator<C> {
+    pub fn with_console(console: C) -> Self {
         Self {
-            storage: [const { None }; STORAGE_SIZE],
+            program: Program::new(),
             stack: Vec::new(),
-            program_counter: 0,
-            variables: [0; NUM_VARIABLES],
+            loops: Vec::new(),
+            program_counter: Some(Self::PROGRAM_START),
+            current_position: None,
+            error_span: None,
+            variables: HashMap::new(),
+            arrays: HashMap::new(),
+            console,
         }
     }
 
-    pub fn process_line(&mut self, line: Line) -> Result<(), Error> {
+    pub fn console(&self) -> &C {
+        &self.console
+    }
+
+    pub fn console_mut(&mut self) -> &mut C {
+        &mut self.console
+    }
+
+    pub fn process_line(&mut self, line: Line) -> Result<Outcome, Error> {
         match line.number().is_some() {
-            true => self.load_line(line),
-            false => self.run_direct(line.statement())?,
+            true => {
+                self.load_line(line)?;
+                Ok(Outcome::Finished)
+            }
+            false => self.run_direct(&line),
         }
+    }
+
+    pub fn load_line(&mut self, line: Line) -> Result<(), Error> {
+        let label = line.number().ok_or(Error::MissingLineNumber)?;
+        self.program.insert(label, line);
 
         Ok(())
     }
 
-    fn load_line(&mut self, line: Line) {
-        debug_assert!(line.number().is_some());
+    pub fn run(&mut self) -> Result<Outcome, Error> {
+        self.restart();
+        self.run_indirect()
+    }
 
-        let label = line.number().unwrap();
-        self.storage[label as usize] = Some(line);
+    pub fn restart(&mut self) {
+        self.stack.clear();
+        self.loops.clear();
+        self.program_counter = Some(Self::PROGRAM_START);
+        self.current_position = None;
+        self.error_span = None;
     }
 
-    fn jump(&mut self, line_number: u8) -> Result<(), Error> {
-        match self.storage.get(line_number as usize) {
-            Some(_) => {
-                self.program_counter = line_number as usize;
+    /// Executes the next statement.
+    ///
+    /// Returns `None` while there are more statements to run.
+    pub fn step(&mut self) -> Result<Option<Outcome>, Error> {
+        if let Some((position, instruction)) = self.next_instruction() {
+            let instruction = instruction.clone();
+            self.current_position = Some(position);
+            self.program_counter = Some(position.next());
+            self.error_span = None;
+
+            if let Some(outcome) = self.execute(&instruction)? {
+                self.program_counter = None;
+                return Ok(Some(outcome));
             }
-            None => Err(Error::UnknownLineNumber)?,
-        };
+        }
+
+        let has_next = self.next_instruction().is_some();
+
+        Ok((!has_next).then_some(Outcome::Finished))
+    }
+
+    /// Returns the number of the stored line executed last.
+    pub fn current_line(&self) -> Option<u16> {
+        self.current_position.and_then(|position| position.line())
+    }
+
+    /// Returns the span of the statement executed last, within the source of its line.
+    ///
+    /// If the statement failed in an operation or a factor, like the `/` of `PRINT 1 / 0`, the
+    /// span of that operation or factor is returned instead.
+    pub fn current_span(&self) -> Option<Span> {
+        let position = self.current_position?;
+        let (_, instruction) = self.program.instruction_at(position)?;
+
+        Some(self.error_span.unwrap_or(instruction.span()))
+    }
+
+    pub fn line(&self, number: u16) -> Option<&Line> {
+        self.program.line(number)
+    }
+
+    pub fn variable(&self, variable: &Variable) -> &Value {
+        self.load_variable(variable)
+    }
+
+    const PROGRAM_START: Position = Position::new(Some(0), 0);
+
+    /// Runs a line without a line number, continuing into the stored program if it jumps there.
+    fn run_direct(&mut self, line: &Line) -> Result<Outcome, Error> {
+        self.program.set_direct(line.statements());
+        self.program_counter = Some(Position::new(None, 0));
+        self.current_position = None;
+        self.error_span = None;
+
+        self.run_indirect()
+    }
+
+    fn next_instruction(&self) -> Option<(Position, &Instruction)> {
+        let program_counter = self.program_counter?;
+        let (position, instruction) = self.program.instruction_at(program_counter)?;
+
+        Some((position, instruction.node()))
+    }
+
+    fn jump(&mut self, line_number: u16) -> Result<(), Error> {
+        if !self.program.contains(line_number) {
+            Err(Error::UnknownLineNumber(line_number))?;
+        }
+
+        self.program_counter = Some(Position::new(Some(line_number), 0));
 
         Ok(())
     }
 
-    fn run_direct(&mut self, statement: &Statement) -> Result<(), Error> {
-        match statement {
-            Statement::Print { expression_list } => {
-                for element in expression_list {
-                    match element {
-                        ExpressionListElement::Expression(expression) => {
-                            let value = self.evaluate_expression(expression);
-                            print!("{value} ");
-                        }
-                        ExpressionListElement::StringLiteral(string_literal) => {
-                            let literal = String::from_utf8_lossy(string_literal.value());
-                            print!("{literal} ",);
-                        }
-                    }
+    fn jump_within_line(&mut self, index: usize) {
+        let line = self.current_position.and_then(|position| position.line());
+        self.program_counter = Some(Position::new(line, index));
+    }
+
+    /// Executes an instruction and returns an outcome if it ended the execution.
+    fn execute(&mut self, instruction: &Instruction) -> Result<Option<Outcome>, Error> {
+        match instruction {
+            Instruction::Statement(statement) => return self.execute_statement(statement),
+            Instruction::If {
+                condition,
+                otherwise,
+            } => {
+                if !self.evaluate_condition(condition)? {
+                    self.jump_within_line(*otherwise);
                 }
-                println!();
             }
-            Statement::If {
-                left,
-                operator,
-                right,
-                then,
-            } => {
-                let left_value = self.evaluate_expression(left);
-                let right_value = self.evaluate_expression(right);
-
-                let condition = match operator {
-                    RelationalOperator::LessThan => left_value < right_value,
-                    RelationalOperator::LessThanOrEqual => left_value <= right_value,
-                    RelationalOperator::GreaterThan => left_value > right_value,
-                    RelationalOperator::GreaterThanOrEqual => left_value >= right_value,
-                    RelationalOperator::Equal => left_value == right_value,
-                    RelationalOperator::NotEqual => left_value != right_value,
-                };
+            Instruction::Jump(index) => self.jump_within_line(*index),
+        }
 
-                if condition {
-                    self.run_direct(then)?;
+        Ok(None)
+    }
+
+    /// Executes a statement and returns an outcome if it ended the execution.
+    fn execute_statement(&mut self, statement: &Statement) -> Result<Option<Outcome>, Error> {
+        match statement {
+            Statement::Print { expression_list } => {
+                let mut text = String::new();
+                for expression in expression_list {
+                    let value = self.evaluate_expression(expression)?;
+                    text += &format!("{value} ");
                 }
+                text += "\n";
+
+                self.console.write(&text)?;
             }
+            Statement::If { .. } => unreachable!("IF is compiled into instructions"),
             Statement::Goto { expression } => {
-                let line_number = Self::to_line_number(self.evaluate_expression(expression))?;
+                let line_number = Self::to_line_number(self.evaluate_integer(expression)?)?;
 
                 self.jump(line_number)?;
             }
             Statement::Input { variable_list } => {
-                let mut buffer = String::new();
-                stdin().read_line(&mut buffer).unwrap();
-
-                let mut nums = Vec::new();
-                for num in buffer.trim().split(" ") {
-                    let num = num.parse::<i16>().map_err(|_| Error::CannotParseNumber)?;
-                    nums.push(num);
-                }
+                // like in classic BASIC, input that does not match the variables is asked again
+                let values = loop {
+                    let buffer = self.console.read_line()?;
+                    match Self::parse_input(&buffer, variable_list) {
+                        Some(values) => break values,
+                        None => self.console.write("?REDO FROM START\n")?,
+                    }
+                };
 
-                if nums.len() != variable_list.len() {
-                    Err(Error::WrongUserInput)?;
+                for (variable, value) in variable_list.iter().zip(values) {
+                    self.store_variable(variable, value)?;
                 }
-
-                for i in 0..nums.len() {
-                    self.store_variable(variable_list[i].identifier(), nums[i]);
+            }
+            Statement::Let { target, expression } => {
+                let value = self.evaluate_expression(expression)?;
+                match target {
+                    Target::Variable(variable) => self.store_variable(variable, value)?,
+                    Target::ArrayElement(element) => self.store_element(element, value)?,
                 }
             }
-            Statement::Let {
-                variable,
-                expression,
-            } => {
-                let value = self.evaluate_expression(expression);
-                self.store_variable(variable.identifier(), value);
+            Statement::Dim { arrays } => {
+                for array in arrays {
+                    let bounds = self
+                        .evaluate_indices(array)?
+                        .into_iter()
+                        .map(|bound| usize::try_from(bound).map_err(|_| Error::IllegalArraySize))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let variable = array.variable();
+                    // declaring an array again replaces it, so that a program can be run twice
+                    let array = Array::new(bounds, variable.is_string())?;
+                    self.arrays.insert(variable.clone(), array);
+                }
             }
             Statement::GoSub { expression } => {
-                let line_number = Self::to_line_number(self.evaluate_expression(expression))?;
+                let line_number = Self::to_line_number(self.evaluate_integer(expression)?)?;
 
                 self.stack.push(self.program_counter);
                 self.jump(line_number)?;
@@ -139,109 +559,714 @@ impl Evaluator {
             Statement::Return => match self.stack.pop() {
                 Some(line_number) => self.program_counter = line_number,
                 None => {
-                    self.program_counter = self.storage.len();
+                    self.program_counter = None;
                 }
             },
             Statement::Clear => {
-                self.storage = [const { None }; STORAGE_SIZE];
+                self.program.clear();
             }
             Statement::List => {
-                self.storage.iter().for_each(|line| {
-                    if let Some(line) = line {
-                        println!("{line}");
+                for line in self.program.lines() {
+                    self.console.write(&format!("{line}\n"))?;
+                }
+            }
+            // carries on from the first line, so that a program can RUN itself again and again
+            Statement::Run => self.restart(),
+            Statement::End => return Ok(Some(Outcome::Stopped)),
+            Statement::Bye => return Ok(Some(Outcome::Exit)),
+            Statement::Remark { .. } | Statement::Comment { .. } => {}
+            Statement::For {
+                variable,
+                from,
+                to,
+                step,
+            } => {
+                let value = self.evaluate_number(from)?;
+                let limit = self.evaluate_number(to)?;
+                let step = match step {
+                    Some(step) => self.evaluate_number(step)?,
+                    None => Value::Integer(1),
+                };
+                self.store_variable(variable, value.clone())?;
+
+                // entering a loop again discards it and every loop nested in it
+                if let Some((index, _)) = self.find_for_loop(Some(variable)) {
+                    self.loops.truncate(index);
+                }
+
+                let for_loop = ForLoop {
+                    variable: variable.clone(),
+                    limit,
+                    step,
+                    body: self.program_counter,
+                };
+                if for_loop.is_running(&value)? {
+                    self.loops.push(ActiveLoop::For(for_loop));
+                } else {
+                    let position = self.find_next(variable)?;
+                    self.program_counter = Some(position.next());
+                }
+            }
+            Statement::Next { variable } => {
+                let (index, for_loop) = self
+                    .find_for_loop(variable.as_ref())
+                    .ok_or(Error::NextWithoutFor)?;
+                self.loops.truncate(index + 1);
+
+                let value = self.load_variable(&for_loop.variable).calculate(
+                    &for_loop.step,
+                    i16::checked_add,
+                    |left, right| left + right,
+                )?;
+                let is_running = for_loop.is_running(&value)?;
+                self.store_variable(&for_loop.variable, value)?;
+
+                if is_running {
+                    self.program_counter = for_loop.body;
+                } else {
+                    self.loops.pop();
+                }
+            }
+            Statement::While { condition } => {
+                let start = self.current_position;
+                if let Some(ActiveLoop::While { start: top }) = self.loops.last() {
+                    if *top == start {
+                        self.loops.pop();
                     }
-                });
+                }
+
+                if self.evaluate_condition(condition)? {
+                    self.loops.push(ActiveLoop::While { start });
+                } else {
+                    let position = self
+                        .find_block_end(
+                            |statement| matches!(statement, Statement::While { .. }),
+                            |statement| matches!(statement, Statement::Wend),
+                        )
+                        .ok_or(Error::WhileWithoutWend)?;
+                    self.program_counter = Some(position.next());
+                }
             }
-            Statement::Run => {
-                self.program_counter = 0;
-                self.run_indirect()?;
+            Statement::Wend => match self.loops.last() {
+                Some(&ActiveLoop::While { start }) => self.program_counter = start,
+                _ => Err(Error::WendWithoutWhile)?,
+            },
+            Statement::Do { condition } => {
+                let start = self.current_position;
+                if let Some(ActiveLoop::Do { start: top }) = self.loops.last() {
+                    if *top == start {
+                        self.loops.pop();
+                    }
+                }
+
+                let is_looping = match condition {
+                    Some(condition) => self.is_looping(condition)?,
+                    None => true,
+                };
+                if is_looping {
+                    self.loops.push(ActiveLoop::Do { start });
+                } else {
+                    let position = self
+                        .find_block_end(
+                            |statement| matches!(statement, Statement::Do { .. }),
+                            |statement| matches!(statement, Statement::Loop { .. }),
+                        )
+                        .ok_or(Error::DoWithoutLoop)?;
+                    self.program_counter = Some(position.next());
+                }
             }
-            Statement::End => {
-                exit(0);
+            Statement::Loop { condition } => {
+                let Some(&ActiveLoop::Do { start }) = self.loops.last() else {
+                    Err(Error::LoopWithoutDo)?
+                };
+
+                let is_looping = match condition {
+                    Some(condition) => self.is_looping(condition)?,
+                    None => true,
+                };
+                if is_looping {
+                    self.program_counter = start;
+                } else {
+                    self.loops.pop();
+                }
             }
         }
 
-        Ok(())
+        Ok(None)
+    }
+
+    /// Finds the innermost `FOR` loop of `variable`, or the innermost one if it is `None`.
+    ///
+    /// Only the `FOR` loops above the innermost `WHILE` or `DO` loop are considered.
+    fn find_for_loop(&self, variable: Option<&Variable>) -> Option<(usize, ForLoop)> {
+        self.loops
+            .iter()
+            .enumerate()
+            .rev()
+            .map_while(|(index, active_loop)| match active_loop {
+                ActiveLoop::For(for_loop) => Some((index, for_loop.clone())),
+                _ => None,
+            })
+            .find(|(_, for_loop)| variable.map_or(true, |variable| for_loop.variable == *variable))
     }
 
-    fn to_line_number(value: i16) -> Result<u8, Error> {
-        match u8::try_from(value) {
-            Ok(line_number) => Ok(line_number),
-            Err(_) => Err(Error::LineNumberOutOfRange)?,
+    /// Finds the `NEXT` closing the `FOR` loop that was just entered.
+    fn find_next(&self, variable: &Variable) -> Result<Position, Error> {
+        let position = self
+            .find_block_end(
+                |statement| matches!(statement, Statement::For { .. }),
+                |statement| matches!(statement, Statement::Next { .. }),
+            )
+            .ok_or(Error::ForWithoutNext)?;
+
+        match self
+            .program
+            .instruction_at(position)
+            .map(|(_, instruction)| instruction.node())
+        {
+            Some(Instruction::Statement(Statement::Next {
+                variable: Some(next_variable),
+            })) if next_variable != variable => Err(Error::ForWithoutNext),
+            _ => Ok(position),
         }
     }
 
-    fn run_indirect(&mut self) -> Result<(), Error> {
-        while self.program_counter < self.storage.len() {
-            let line = match &self.storage[self.program_counter] {
-                Some(line) => line,
-                None => {
-                    self.program_counter += 1;
-                    continue;
-                }
+    /// Finds the statement closing the block whose opening statement was just run,
+    /// skipping the blocks nested in it.
+    fn find_block_end(
+        &self,
+        is_opening: impl Fn(&Statement) -> bool,
+        is_closing: impl Fn(&Statement) -> bool,
+    ) -> Option<Position> {
+        let start = self.program_counter?;
+        let mut depth = 0;
+
+        for (position, instruction) in self.program.instructions_from(start) {
+            let Instruction::Statement(statement) = instruction.node() else {
+                continue;
             };
 
-            self.run_direct(&line.statement().clone())?;
-            self.program_counter += 1;
+            if is_opening(statement) {
+                depth += 1;
+            } else if is_closing(statement) {
+                if depth == 0 {
+                    return Some(position);
+                }
+                depth -= 1;
+            }
         }
 
-        Ok(())
+        None
+    }
+
+    fn evaluate_condition(&mut self, condition: &Expression) -> Result<bool, Error> {
+        let value = self.evaluate_number(condition)?;
+        Ok(value.compare(&Value::Integer(0))?.is_ne())
     }
 
-    fn evaluate_expression(&self, expression: &Expression) -> i16 {
-        let term = expression.term();
-        let mut result = self.evaluate_term(term);
+    fn is_looping(&mut self, condition: &LoopCondition) -> Result<bool, Error> {
+        match condition {
+            LoopCondition::While(condition) => self.evaluate_condition(condition),
+            LoopCondition::Until(condition) => Ok(!self.evaluate_condition(condition)?),
+        }
+    }
+
+    fn to_line_number(value: i16) -> Result<u16, Error> {
+        match u16::try_from(value) {
+            Ok(line_number @ 1..=MAX_LINE_NUMBER) => Ok(line_number),
+            _ => Err(Error::LineNumberOutOfRange)?,
+        }
+    }
 
-        if let Some(AdditiveOperator::Subtraction) = expression.unary_operator() {
-            result = -result;
+    fn run_indirect(&mut self) -> Result<Outcome, Error> {
+        loop {
+            if let Some(outcome) = self.step()? {
+                return Ok(outcome);
+            }
+        }
+    }
+
+    /// Evaluates an expression that must give a number.
+    fn evaluate_number(&mut self, expression: &Expression) -> Result<Value, Error> {
+        let value = self.evaluate_expression(expression)?;
+        if let Value::String(_) = value {
+            Err(Error::TypeMismatch)?;
+        }
+
+        Ok(value)
+    }
+
+    fn evaluate_integer(&mut self, expression: &Expression) -> Result<i16, Error> {
+        self.evaluate_expression(expression)?.to_integer()
+    }
+
+    fn evaluate_expression(&mut self, expression: &Expression) -> Result<Value, Error> {
+        let value = match expression {
+            Expression::Arithmetic(expression) => {
+                return self.evaluate_arithmetic_expression(expression);
+            }
+            Expression::Comparison {
+                left,
+                operator,
+                right,
+            } => {
+                let left_value = self.evaluate_arithmetic_expression(left)?;
+                let right_value = self.evaluate_arithmetic_expression(right)?;
+                let ordering = left_value.compare(&right_value)?;
+
+                Self::truth(match operator {
+                    RelationalOperator::LessThan => ordering.is_lt(),
+                    RelationalOperator::LessThanOrEqual => ordering.is_le(),
+                    RelationalOperator::GreaterThan => ordering.is_gt(),
+                    RelationalOperator::GreaterThanOrEqual => ordering.is_ge(),
+                    RelationalOperator::Equal => ordering.is_eq(),
+                    RelationalOperator::NotEqual => ordering.is_ne(),
+                })
+            }
+            Expression::Not(expression) => Self::truth(!self.evaluate_condition(expression)?),
+            Expression::And(left, right) => {
+                Self::truth(self.evaluate_condition(left)? && self.evaluate_condition(right)?)
+            }
+            Expression::Or(left, right) => {
+                Self::truth(self.evaluate_condition(left)? || self.evaluate_condition(right)?)
+            }
+        };
+
+        Ok(Value::Integer(value))
+    }
+
+    /// Converts a boolean to the value BASIC uses for it: -1 for true and 0 for false.
+    fn truth(value: bool) -> i16 {
+        if value {
+            -1
+        } else {
+            0
+        }
+    }
+
+    fn evaluate_arithmetic_expression(
+        &mut self,
+        expression: &ArithmeticExpression,
+    ) -> Result<Value, Error> {
+        let mut result = self.evaluate_term(expression.term())?;
+        if let Some(operator) = expression.unary_operator() {
+            result = Self::apply_sign(operator, result)?;
         }
 
         for (operator, term) in expression.others() {
-            let value = self.evaluate_term(term);
+            let value = self.evaluate_term(term)?;
+            let value = Self::apply_additive_operator(operator.node(), result, value);
+            result = self.locate(value, operator.span())?;
+        }
 
-            match operator {
-                AdditiveOperator::Addition => result += value,
-                AdditiveOperator::Subtraction => result -= value,
+        Ok(result)
+    }
+
+    fn apply_additive_operator(
+        operator: &AdditiveOperator,
+        left: Value,
+        right: Value,
+    ) -> Result<Value, Error> {
+        match (operator, left, right) {
+            (AdditiveOperator::Addition, Value::String(mut left), Value::String(right)) => {
+                left.extend(right);
+                Ok(Value::String(left))
+            }
+            (AdditiveOperator::Addition, left, right) => {
+                left.calculate(&right, i16::checked_add, |left, right| left + right)
+            }
+            (AdditiveOperator::Subtraction, left, right) => {
+                left.calculate(&right, i16::checked_sub, |left, right| left - right)
             }
         }
+    }
 
-        result
+    /// Applies a unary `+` or `-` to a number.
+    fn apply_sign(operator: &AdditiveOperator, value: Value) -> Result<Value, Error> {
+        match (operator, value) {
+            (_, Value::String(_)) => Err(Error::TypeMismatch),
+            (AdditiveOperator::Addition, value) => Ok(value),
+            (AdditiveOperator::Subtraction, Value::Integer(value)) => Ok(value
+                .checked_neg()
+                .map_or(Value::Float(-f64::from(value)), Value::Integer)),
+            (AdditiveOperator::Subtraction, Value::Float(value)) => Ok(Value::Float(-value)),
+        }
     }
 
-    fn evaluate_term(&self, term: &Term) -> i16 {
-        let factor = term.factor();
-        let mut result = self.evaluate_factor(factor);
+    fn evaluate_term(&mut self, term: &Term) -> Result<Value, Error> {
+        match term {
+            Term::Factor(factor) => {
+                let value = self.evaluate_factor(factor.node());
+                self.locate(value, factor.span())
+            }
+            Term::Operation {
+                left,
+                operator,
+                right,
+            } => {
+                let (left, right) = (self.evaluate_term(left)?, self.evaluate_term(right)?);
+                let value = Self::apply_multiplicative_operator(operator.node(), left, right);
+                self.locate(value, operator.span())
+            }
+        }
+    }
 
-        for (operator, factor) in term.operations() {
-            let value = self.evaluate_factor(factor);
+    fn apply_multiplicative_operator(
+        operator: &MultiplicativeOperator,
+        left: Value,
+        right: Value,
+    ) -> Result<Value, Error> {
+        match operator {
+            MultiplicativeOperator::Multiplication => {
+                left.calculate(&right, i16::checked_mul, |left, right| left * right)
+            }
+            MultiplicativeOperator::Division => {
+                let (left, right) = (left.to_float()?, right.to_float()?);
+                if right == 0.0 {
+                    Err(Error::DivisionByZero)?;
+                }
 
-            match operator {
-                MultiplicativeOperator::Multiplication => result *= value,
-                MultiplicativeOperator::Division => result /= value,
+                Value::float(left / right)
+            }
+            // these operators work on integers, dropping the fraction of floats
+            MultiplicativeOperator::IntegerDivision | MultiplicativeOperator::Modulo => {
+                let (left, right) = (left.to_integer()?, right.to_integer()?);
+                if right == 0 {
+                    Err(Error::DivisionByZero)?;
+                }
+
+                match operator {
+                    MultiplicativeOperator::Modulo => {
+                        // the remainder of `i16::MIN / -1` is zero even though the quotient
+                        // overflows
+                        Ok(Value::Integer(left.wrapping_rem(right)))
+                    }
+                    _ => Ok(Value::Integer(
+                        left.checked_div(right).ok_or(Error::Overflow)?,
+                    )),
+                }
             }
         }
+    }
+
+    /// Remembers `span` as where the error `result` may hold was raised, unless a node nested
+    /// in it was remembered already.
+    fn locate<T>(&mut self, result: Result<T, Error>, span: Span) -> Result<T, Error> {
+        if result.is_err() {
+            self.error_span.get_or_insert(span);
+        }
 
         result
     }
 
-    fn evaluate_factor(&self, factor: &Factor) -> i16 {
-        match factor {
-            Factor::Variable(variable) => {
-                let identifier = variable.identifier();
-                self.load_variable(identifier)
+    fn evaluate_factor(&mut self, factor: &Factor) -> Result<Value, Error> {
+        let value = match factor {
+            Factor::Variable(variable) => self.load_variable(variable).clone(),
+            Factor::ArrayElement(element) => self.load_element(element)?,
+            Factor::FunctionCall(call) => {
+                let arguments = call
+                    .arguments()
+                    .iter()
+                    .map(|argument| self.evaluate_expression(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Self::call(call.function(), &arguments)?
+            }
+            Factor::NumberLiteral(number) => Value::Integer(number.value()),
+            Factor::FloatLiteral(number) => Value::Float(number.value()),
+            Factor::StringLiteral(string) => Value::String(string.value().clone()),
+            Factor::Expression(expression) => self.evaluate_expression(expression)?,
+            Factor::Unary { operator, factor } => {
+                Self::apply_sign(operator, self.evaluate_factor(factor)?)?
+            }
+            Factor::Power { base, exponent } => {
+                let base = self.evaluate_factor(base)?;
+                let exponent = self.evaluate_factor(exponent)?;
+                Self::power(&base, &exponent)?
             }
-            Factor::NumberLiteral(number) => number.value(),
-            Factor::Expression(expression) => self.evaluate_expression(expression),
+        };
+
+        Ok(value)
+    }
+
+    /// Calls a built-in function, whose number of arguments the parser has checked.
+    fn call(function: Function, arguments: &[Value]) -> Result<Value, Error> {
+        let argument = &arguments[0];
+        let value = match function {
+            Function::Abs => match argument {
+                Value::Integer(value) => value
+                    .checked_abs()
+                    .map_or(Value::Float(f64::from(*value).abs()), Value::Integer),
+                _ => Value::Float(argument.to_float()?.abs()),
+            },
+            Function::Sgn => Value::Integer(match argument.compare(&Value::Integer(0))? {
+                Ordering::Less => -1,
+                Ordering::Equal => 0,
+                Ordering::Greater => 1,
+            }),
+            Function::Int => match argument {
+                Value::Integer(value) => Value::Integer(*value),
+                _ => Value::Float(argument.to_float()?.floor()),
+            },
+            Function::Sqr => match argument.to_float()? {
+                value if value < 0.0 => Err(Error::IllegalFunctionCall(function))?,
+                value => Value::Float(value.sqrt()),
+            },
+            Function::Sin => Value::Float(argument.to_float()?.sin()),
+            Function::Cos => Value::Float(argument.to_float()?.cos()),
+            Function::Atn => Value::Float(argument.to_float()?.atan()),
+            Function::Exp => Value::Float(argument.to_float()?.exp()),
+            Function::Log => match argument.to_float()? {
+                value if value <= 0.0 => Err(Error::IllegalFunctionCall(function))?,
+                value => Value::Float(value.ln()),
+            },
+            Function::Len => {
+                let length = argument.as_bytes()?.len();
+                Self::count(length)
+            }
+            Function::Left => {
+                let text = argument.as_bytes()?;
+                let length = Self::to_count(function, &arguments[1])?.min(text.len());
+                Value::String(text[..length].to_vec())
+            }
+            Function::Right => {
+                let text = argument.as_bytes()?;
+                let length = Self::to_count(function, &arguments[1])?.min(text.len());
+                Value::String(text[text.len() - length..].to_vec())
+            }
+            Function::Mid => {
+                let text = argument.as_bytes()?;
+                let start = Self::to_position(function, &arguments[1])?;
+                let length = match arguments.get(2) {
+                    Some(length) => Self::to_count(function, length)?,
+                    None => usize::MAX,
+                };
+
+                // starting past the end gives the empty string
+                let rest = text.get(start - 1..).unwrap_or_default();
+                Value::String(rest[..length.min(rest.len())].to_vec())
+            }
+            Function::Chr => {
+                let code = u8::try_from(argument.to_integer()?)
+                    .map_err(|_| Error::IllegalFunctionCall(function))?;
+                Value::String(vec![code])
+            }
+            Function::Asc => match argument.as_bytes()?.first() {
+                Some(&code) => Value::Integer(i16::from(code)),
+                None => Err(Error::IllegalFunctionCall(function))?,
+            },
+            // gives the text `PRINT` shows for the number
+            Function::Str => match argument {
+                Value::String(_) => Err(Error::TypeMismatch)?,
+                number => Value::String(number.to_string().into_bytes()),
+            },
+            Function::Val => Self::number_prefix(argument.as_bytes()?),
+            Function::Instr => {
+                let (start, text, pattern) = match arguments {
+                    [text, pattern] => (1, text, pattern),
+                    [start, text, pattern] => (Self::to_position(function, start)?, text, pattern),
+                    _ => unreachable!("the parser checks the number of arguments"),
+                };
+                let (text, pattern) = (text.as_bytes()?, pattern.as_bytes()?);
+
+                let position = if start > text.len() {
+                    None
+                } else if pattern.is_empty() {
+                    Some(start)
+                } else {
+                    text[start - 1..]
+                        .windows(pattern.len())
+                        .position(|window| window == pattern)
+                        .map(|index| start + index)
+                };
+                Self::count(position.unwrap_or(0))
+            }
+        };
+
+        match value {
+            Value::Float(value) if !value.is_finite() => Err(Error::Overflow),
+            value => Ok(value),
+        }
+    }
+
+    /// Converts a length or position into an integer, or into a float if it is too large.
+    fn count(value: usize) -> Value {
+        i16::try_from(value).map_or(Value::Float(value as f64), Value::Integer)
+    }
+
+    /// Converts a count argument of `function`, like the length of `LEFT$`.
+    fn to_count(function: Function, value: &Value) -> Result<usize, Error> {
+        usize::try_from(value.to_integer()?).map_err(|_| Error::IllegalFunctionCall(function))
+    }
+
+    /// Converts a 1-based position argument of `function`, like the start of `MID$`.
+    fn to_position(function: Function, value: &Value) -> Result<usize, Error> {
+        match Self::to_count(function, value)? {
+            0 => Err(Error::IllegalFunctionCall(function)),
+            position => Ok(position),
+        }
+    }
+
+    /// Reads the number `text` starts with like `VAL`, giving 0 if there is none.
+    ///
+    /// Leading spaces are skipped and anything after the number is ignored, so `" 12 APPLES"`
+    /// gives 12.
+    fn number_prefix(text: &[u8]) -> Value {
+        let start = text
+            .iter()
+            .position(|ch| !ch.is_ascii_whitespace())
+            .unwrap_or(text.len());
+        let text = &text[start..];
+        let digits = |mut end: usize| {
+            while text.get(end).is_some_and(u8::is_ascii_digit) {
+                end += 1;
+            }
+            end
+        };
+
+        let mut end = match text.first() {
+            Some(b'+' | b'-') => digits(1),
+            _ => digits(0),
+        };
+        if text.get(end) == Some(&b'.') {
+            end = digits(end + 1);
+        }
+        if let Some(b'E' | b'e') = text.get(end) {
+            let exponent = match text.get(end + 1) {
+                Some(b'+' | b'-') => end + 2,
+                _ => end + 1,
+            };
+            if text.get(exponent).is_some_and(u8::is_ascii_digit) {
+                end = digits(exponent);
+            }
+        }
+
+        std::str::from_utf8(&text[..end])
+            .ok()
+            .and_then(Self::parse_number)
+            .unwrap_or(Value::Integer(0))
+    }
+
+    /// Splits a line typed for `INPUT` into the values of `variable_list`, or returns `None` if
+    /// it does not hold one value of the right type for each variable.
+    fn parse_input(buffer: &str, variable_list: &[Variable]) -> Option<Vec<Value>> {
+        let buffer = buffer.trim();
+
+        // values are separated by commas, or by spaces if there are no commas
+        let mut fields: Vec<&str> = buffer.split(',').map(str::trim).collect();
+        if fields.len() == 1 && variable_list.len() > 1 {
+            fields = buffer.split_whitespace().collect();
+        }
+        if fields.len() != variable_list.len() {
+            return None;
+        }
+
+        variable_list
+            .iter()
+            .zip(fields)
+            .map(|(variable, field)| match variable.is_string() {
+                true => Some(Value::String(field.as_bytes().to_vec())),
+                false => Self::parse_number(field),
+            })
+            .collect()
+    }
+
+    /// Parses a number typed for `INPUT`.
+    fn parse_number(text: &str) -> Option<Value> {
+        if let Ok(value) = text.parse() {
+            return Some(Value::Integer(value));
         }
+
+        text.parse()
+            .ok()
+            .filter(|value: &f64| value.is_finite())
+            .map(Value::Float)
     }
 
-    fn load_variable(&self, identifier: u8) -> i16 {
-        let offset = (identifier - b'A') as usize;
-        self.variables[offset]
+    /// Raises `base` to `exponent`, giving an integer if both are integers, `exponent` is not
+    /// negative and the result fits in an integer.
+    fn power(base: &Value, exponent: &Value) -> Result<Value, Error> {
+        if let (Value::Integer(base), Value::Integer(exponent)) = (base, exponent) {
+            let value = u32::try_from(*exponent)
+                .ok()
+                .and_then(|exponent| base.checked_pow(exponent));
+            if let Some(value) = value {
+                return Ok(Value::Integer(value));
+            }
+        }
+
+        let (base, exponent) = (base.to_float()?, exponent.to_float()?);
+        if base == 0.0 && exponent < 0.0 {
+            Err(Error::DivisionByZero)?;
+        }
+
+        Value::float(base.powf(exponent))
     }
 
-    fn store_variable(&mut self, identifier: u8, value: i16) {
-        let offset = (identifier - b'A') as usize;
-        self.variables[offset] = value;
+    /// Fails unless `value` has the type `variable` holds.
+    fn check_type(variable: &Variable, value: &Value) -> Result<(), Error> {
+        if variable.is_string() != matches!(value, Value::String(_)) {
+            Err(Error::TypeMismatch)?;
+        }
+
+        Ok(())
+    }
+
+    fn load_variable(&self, variable: &Variable) -> &Value {
+        static ZERO: Value = Value::Integer(0);
+        static EMPTY_STRING: Value = Value::String(Vec::new());
+
+        self.variables
+            .get(variable)
+            .unwrap_or(if variable.is_string() {
+                &EMPTY_STRING
+            } else {
+                &ZERO
+            })
+    }
+
+    fn store_variable(&mut self, variable: &Variable, value: Value) -> Result<(), Error> {
+        Self::check_type(variable, &value)?;
+        self.variables.insert(variable.clone(), value);
+
+        Ok(())
+    }
+
+    fn evaluate_indices(&mut self, element: &ArrayElement) -> Result<Vec<i16>, Error> {
+        element
+            .indices()
+            .iter()
+            .map(|index| self.evaluate_integer(index))
+            .collect()
+    }
+
+    fn load_element(&mut self, element: &ArrayElement) -> Result<Value, Error> {
+        let indices = self.evaluate_indices(element)?;
+        let variable = element.variable();
+        let array = self
+            .arrays
+            .get(variable)
+            .ok_or_else(|| Error::UndimensionedArray(variable.to_string()))?;
+
+        Ok(array.values[array.offset(&indices)?].clone())
+    }
+
+    fn store_element(&mut self, element: &ArrayElement, value: Value) -> Result<(), Error> {
+        let indices = self.evaluate_indices(element)?;
+        let variable = element.variable();
+        Self::check_type(variable, &value)?;
+        let array = self
+            .arrays
+            .get_mut(variable)
+            .ok_or_else(|| Error::UndimensionedArray(variable.to_string()))?;
+        let offset = array.offset(&indices)?;
+        array.values[offset] = value;
+
+        Ok(())
+    }
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -0,0 +1,1384 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::ast::{Line, Variable};
+use crate::console::{Console, StdConsole};
+use crate::evaluator::{self, Evaluator, Outcome, Value};
+use crate::lexer::{self, lex};
+use crate::parser::{self, Parser};
+use crate::span::{Span, Spanned};
+use crate::token::Token;
+
+/// Error with the span of the source text it was found in.
+///
+/// The span of a runtime error covers the operation or factor that failed, or else the statement,
+/// in the source of the stored line given by [`Interpreter::current_line`] or in the line run
+/// directly.
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    Lexer(Spanned<lexer::Error>),
+    Parser(Spanned<parser::Error>),
+    Runtime(Spanned<evaluator::Error>),
+}
+
+impl Error {
+    pub fn span(&self) -> Span {
+        match self {
+            Error::Lexer(error) => error.span(),
+            Error::Parser(error) => error.span(),
+            Error::Runtime(error) => error.span(),
+        }
+    }
+}
+
+/// Prints the terse message of a classic BASIC, like `?SYNTAX ERROR`.
+///
+/// The error it was caused by, which explains it, is available through
+/// [`std::error::Error::source`].
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Lexer(error) => match error.node() {
+                lexer::Error::NumberOutOfRange { .. } => write!(f, "?OVERFLOW"),
+                _ => write!(f, "?SYNTAX ERROR"),
+            },
+            Error::Parser(_) => write!(f, "?SYNTAX ERROR"),
+            Error::Runtime(error) => match error.node() {
+                evaluator::Error::LineNumberOutOfRange => write!(f, "?ILLEGAL LINE NUMBER"),
+                evaluator::Error::MissingLineNumber => write!(f, "?DIRECT STATEMENT IN FILE"),
+                evaluator::Error::UnknownLineNumber(line_number) => {
+                    write!(f, "?UNDEFINED LINE {}", line_number)
+                }
+                evaluator::Error::NextWithoutFor => write!(f, "?NEXT WITHOUT FOR"),
+                evaluator::Error::ForWithoutNext => write!(f, "?FOR WITHOUT NEXT"),
+                evaluator::Error::WendWithoutWhile => write!(f, "?WEND WITHOUT WHILE"),
+                evaluator::Error::WhileWithoutWend => write!(f, "?WHILE WITHOUT WEND"),
+                evaluator::Error::LoopWithoutDo => write!(f, "?LOOP WITHOUT DO"),
+                evaluator::Error::DoWithoutLoop => write!(f, "?DO WITHOUT LOOP"),
+                evaluator::Error::DivisionByZero => write!(f, "?DIVISION BY ZERO"),
+                evaluator::Error::Overflow => write!(f, "?OVERFLOW"),
+                evaluator::Error::TypeMismatch => write!(f, "?TYPE MISMATCH"),
+                evaluator::Error::IllegalFunctionCall(_) => write!(f, "?ILLEGAL FUNCTION CALL"),
+                evaluator::Error::UndimensionedArray(_) => write!(f, "?UNDIMENSIONED ARRAY"),
+                evaluator::Error::SubscriptOutOfRange
+                | evaluator::Error::WrongNumberOfSubscripts { .. } => {
+                    write!(f, "?SUBSCRIPT OUT OF RANGE")
+                }
+                evaluator::Error::IllegalArraySize => write!(f, "?ILLEGAL QUANTITY"),
+                evaluator::Error::Io(_) => write!(f, "?I/O ERROR"),
+            },
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Lexer(error) => Some(error.node()),
+            Error::Parser(error) => Some(error.node()),
+            Error::Runtime(error) => Some(error.node()),
+        }
+    }
+}
+
+impl From<Spanned<lexer::Error>> for Error {
+    fn from(error: Spanned<lexer::Error>) -> Self {
+        Error::Lexer(error)
+    }
+}
+
+impl From<Spanned<parser::Error>> for Error {
+    fn from(error: Spanned<parser::Error>) -> Self {
+        Error::Parser(error)
+    }
+}
+
+impl From<Spanned<evaluator::Error>> for Error {
+    fn from(error: Spanned<evaluator::Error>) -> Self {
+        Error::Runtime(error)
+    }
+}
+
+/// Error found while loading a program, with the 1-based line of the source it occurred on.
+#[derive(Debug, PartialEq)]
+pub struct LoadError {
+    line: usize,
+    error: Error,
+}
+
+impl LoadError {
+    pub fn new(line: usize, error: Error) -> Self {
+        Self { line, error }
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.line, self.error)
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// The lines of a program that could be parsed, and the errors of those that could not.
+#[derive(Debug, PartialEq, Default)]
+pub struct ParsedProgram {
+    lines: Vec<(usize, Line)>,
+    diagnostics: Vec<LoadError>,
+}
+
+impl ParsedProgram {
+    /// Returns the parsed lines, each with the 1-based line of the source it was read from.
+    pub fn lines(&self) -> &[(usize, Line)] {
+        &self.lines
+    }
+
+    pub fn diagnostics(&self) -> &[LoadError] {
+        &self.diagnostics
+    }
+
+    pub fn into_parts(self) -> (Vec<(usize, Line)>, Vec<LoadError>) {
+        (self.lines, self.diagnostics)
+    }
+}
+
+/// Parses every line of `source`, carrying on after the lines that fail to parse.
+///
+/// A leading `#!` line is skipped so that scripts can be executed directly, and so are blank
+/// lines.
+pub fn parse_program(source: &str) -> ParsedProgram {
+    let mut program = ParsedProgram::default();
+
+    for (index, code) in source.lines().enumerate() {
+        let is_shebang = index == 0 && code.starts_with("#!");
+        if is_shebang || code.trim().is_empty() {
+            continue;
+        }
+
+        match parse_line(code) {
+            Ok(line) => program.lines.push((index + 1, line)),
+            Err(error) => program.diagnostics.push(LoadError::new(index + 1, error)),
+        }
+    }
+
+    program
+}
+
+fn parse_line(code: &str) -> Result<Line, Error> {
+    let tokens = lex(code.as_bytes())?;
+    let mut parser = Parser::new(VecDeque::from(tokens));
+    let line = parser.parse_line()?;
+
+    Ok(line.with_source(code))
+}
+
+/// Embeddable Tiny BASIC interpreter.
+pub struct Interpreter<C: Console = StdConsole> {
+    evaluator: Evaluator<C>,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::with_console(StdConsole)
+    }
+}
+
+impl<C: Console> Interpreter<C> {
+    /// Creates an interpreter whose `PRINT` and `INPUT` go through `console`.
+    pub fn with_console(console: C) -> Self {
+        Self {
+            evaluator: Evaluator::with_console(console),
+        }
+    }
+
+    pub fn console(&self) -> &C {
+        self.evaluator.console()
+    }
+
+    pub fn console_mut(&mut self) -> &mut C {
+        self.evaluator.console_mut()
+    }
+
+    /// Executes a line as if it was typed at the prompt.
+    ///
+    /// Numbered lines are stored in the program, the others are run immediately.
+    pub fn execute(&mut self, code: &str) -> Result<Outcome, Error> {
+        let line = parse_line(code)?;
+        let outcome = self
+            .evaluator
+            .process_line(line)
+            .map_err(|error| self.runtime_error(error))?;
+
+        Ok(outcome)
+    }
+
+    /// Stores every numbered line of `source` in the program without running it.
+    ///
+    /// Nothing is stored if any line is invalid; the errors of all invalid lines are returned
+    /// instead. See [`parse_program`] for the lines that are skipped.
+    pub fn load(&mut self, source: &str) -> Result<(), Vec<LoadError>> {
+        let (lines, mut diagnostics) = parse_program(source).into_parts();
+        for (index, line) in &lines {
+            if line.number().is_none() {
+                let code = line.source().unwrap_or_default();
+                let span = Span::new(0, code.trim_end().len());
+                let error = Spanned::new(evaluator::Error::MissingLineNumber, span);
+                diagnostics.push(LoadError::new(*index, error.into()));
+            }
+        }
+
+        if !diagnostics.is_empty() {
+            diagnostics.sort_by_key(LoadError::line);
+            return Err(diagnostics);
+        }
+
+        for (_, line) in lines {
+            self.evaluator
+                .load_line(line)
+                .expect("numbered lines are stored");
+        }
+
+        Ok(())
+    }
+
+    /// Runs the stored program from its first line.
+    pub fn run(&mut self) -> Result<Outcome, Error> {
+        let outcome = self
+            .evaluator
+            .run()
+            .map_err(|error| self.runtime_error(error))?;
+
+        Ok(outcome)
+    }
+
+    /// Executes the next statement.
+    ///
+    /// Returns `None` while there are more statements to run.
+    pub fn step(&mut self) -> Result<Option<Outcome>, Error> {
+        let outcome = self
+            .evaluator
+            .step()
+            .map_err(|error| self.runtime_error(error))?;
+
+        Ok(outcome)
+    }
+
+    /// Returns the number of the stored line executed last, e.g. the one that raised an error.
+    pub fn current_line(&self) -> Option<u16> {
+        self.evaluator.current_line()
+    }
+
+    /// Moves execution back to the first stored line.
+    pub fn restart(&mut self) {
+        self.evaluator.restart();
+    }
+
+    /// Returns the value of the numeric variable `name`, or `None` if it is not a valid name of
+    /// a numeric variable.
+    ///
+    /// Integers are converted to floats; [`Interpreter::value`] tells them apart.
+    pub fn variable(&self, name: &str) -> Option<f64> {
+        match self.value(name)? {
+            Value::Integer(value) => Some(f64::from(*value)),
+            Value::Float(value) => Some(*value),
+            Value::String(_) => None,
+        }
+    }
+
+    /// Returns the value of the string variable `name`, like `A$`, or `None` if it is not a valid
+    /// name of a string variable.
+    pub fn string_variable(&self, name: &str) -> Option<&[u8]> {
+        match self.value(name)? {
+            Value::String(value) => Some(value),
+            Value::Integer(_) | Value::Float(_) => None,
+        }
+    }
+
+    /// Returns the value of the variable `name`, or `None` if it is not a valid variable name.
+    pub fn value(&self, name: &str) -> Option<&Value> {
+        let tokens = lex(name.as_bytes()).ok()?;
+        let variable = match tokens.as_slice() {
+            [token] => match token.node() {
+                Token::Variable { name } => Variable::new(name.as_str()),
+                Token::StringVariable { name } => Variable::string(name.as_str()),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        Some(self.evaluator.variable(&variable))
+    }
+
+    /// Returns the source text of the stored line `number`.
+    pub fn line_source(&self, number: u16) -> Option<&str> {
+        self.evaluator.line(number)?.source()
+    }
+
+    fn runtime_error(&self, error: evaluator::Error) -> Error {
+        let span = self.evaluator.current_span().unwrap_or_default();
+        Error::Runtime(Spanned::new(error, span))
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Function;
+    use crate::console::BufferConsole;
+    use crate::evaluator::Value;
+    use crate::token::Token;
+
+    #[test]
+    fn run_loaded_program_stores_variables() {
+        let source = "10 LET A = 2\n20 LET B = A * 3\n\n30 LET A = A + B\n";
+        let mut interpreter = Interpreter::new();
+
+        interpreter.load(source).unwrap();
+        interpreter.run().unwrap();
+
+        assert_eq!(Some(8.0), interpreter.variable("A"));
+        assert_eq!(Some(6.0), interpreter.variable("b"));
+    }
+
+    #[test]
+    fn step_executes_one_line_at_a_time() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load("10 LET A = 1\n20 LET A = 2").unwrap();
+
+        assert_eq!(Ok(None), interpreter.step());
+        assert_eq!(Some(1.0), interpreter.variable("A"));
+        assert_eq!(Ok(Some(Outcome::Finished)), interpreter.step());
+        assert_eq!(Some(2.0), interpreter.variable("A"));
+    }
+
+    #[test]
+    fn goto_continues_at_target_line() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .load("10 GOTO 30\n20 LET A = 1\n30 LET B = 1")
+            .unwrap();
+
+        interpreter.run().unwrap();
+
+        assert_eq!(Some(0.0), interpreter.variable("A"));
+        assert_eq!(Some(1.0), interpreter.variable("B"));
+    }
+
+    #[test]
+    fn gosub_and_return_across_wide_line_numbers() {
+        let source =
+            "1000 GOSUB 32000\n1010 LET B = A + 1\n1020 END\n32000 LET A = 5\n32767 RETURN";
+        let mut interpreter = Interpreter::new();
+        interpreter.load(source).unwrap();
+
+        let actual = interpreter.run();
+
+        assert_eq!(Ok(Outcome::Stopped), actual);
+        assert_eq!(Some(6.0), interpreter.variable("B"));
+    }
+
+    #[test]
+    fn goto_unknown_line_returns_error() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load("10 GOTO 300").unwrap();
+
+        let actual = interpreter.run();
+
+        assert_eq!(
+            Err(Error::Runtime(
+                evaluator::Error::UnknownLineNumber(300).into()
+            )),
+            actual
+        );
+    }
+
+    #[test]
+    fn list_prints_lines_in_order() {
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+        interpreter.execute("2000 END").unwrap();
+        interpreter.execute("100 GOTO 2000").unwrap();
+
+        interpreter.execute("LIST").unwrap();
+
+        assert_eq!("100 GOTO 2000\n2000 END\n", interpreter.console().output());
+    }
+
+    #[test]
+    fn for_loop_runs_body_for_each_value() {
+        let source = "10 FOR I = 1 TO 3\n20 PRINT I\n30 NEXT I\n40 PRINT I";
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+        interpreter.load(source).unwrap();
+
+        interpreter.run().unwrap();
+
+        assert_eq!("1 \n2 \n3 \n4 \n", interpreter.console().output());
+    }
+
+    #[test]
+    fn for_loop_with_negative_step_counts_down() {
+        let source = "10 FOR I = 10 TO 1 STEP -3\n20 PRINT I\n30 NEXT";
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+        interpreter.load(source).unwrap();
+
+        interpreter.run().unwrap();
+
+        assert_eq!("10 \n7 \n4 \n1 \n", interpreter.console().output());
+    }
+
+    #[test]
+    fn nested_for_loops_run_inner_loop_each_time() {
+        let source = "10 FOR I = 1 TO 3\n20 FOR J = I TO 3\n30 LET C = C + 1\n40 NEXT J\n50 NEXT I";
+        let mut interpreter = Interpreter::new();
+        interpreter.load(source).unwrap();
+
+        interpreter.run().unwrap();
+
+        assert_eq!(Some(6.0), interpreter.variable("C"));
+    }
+
+    #[test]
+    fn for_loop_with_empty_range_skips_body() {
+        let source =
+            "10 FOR I = 5 TO 1\n20 FOR J = 1 TO 2\n30 PRINT J\n40 NEXT\n50 NEXT I\n60 PRINT I";
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+        interpreter.load(source).unwrap();
+
+        interpreter.run().unwrap();
+
+        assert_eq!("5 \n", interpreter.console().output());
+    }
+
+    #[test]
+    fn next_without_for_returns_error() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load("10 FOR I = 1 TO 2\n20 NEXT J").unwrap();
+
+        let actual = interpreter.run();
+
+        assert_eq!(
+            Err(Error::Runtime(evaluator::Error::NextWithoutFor.into())),
+            actual
+        );
+    }
+
+    #[test]
+    fn while_loop_runs_while_condition_holds() {
+        let source = "10 WHILE A < 3\n20 LET A = A + 1\n30 PRINT A\n40 WEND\n50 PRINT 0";
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+        interpreter.load(source).unwrap();
+
+        interpreter.run().unwrap();
+
+        assert_eq!("1 \n2 \n3 \n0 \n", interpreter.console().output());
+    }
+
+    #[test]
+    fn while_loop_with_false_condition_skips_nested_loops() {
+        let source = "10 WHILE A > 0\n20 WHILE B = 0\n30 WEND\n40 LET C = 1\n50 WEND";
+        let mut interpreter = Interpreter::new();
+        interpreter.load(source).unwrap();
+
+        interpreter.run().unwrap();
+
+        assert_eq!(Some(0.0), interpreter.variable("C"));
+    }
+
+    #[test]
+    fn do_loop_until_runs_body_at_least_once() {
+        let source = "10 DO\n20 LET A = A + 1\n30 LOOP UNTIL A > 0";
+        let mut interpreter = Interpreter::new();
+        interpreter.load(source).unwrap();
+
+        interpreter.run().unwrap();
+
+        assert_eq!(Some(1.0), interpreter.variable("A"));
+    }
+
+    #[test]
+    fn do_while_loop_checks_condition_first() {
+        let source = "10 DO WHILE A < 5\n20 FOR I = 1 TO 2\n30 LET A = A + 1\n40 NEXT I\n50 LOOP\n60 DO WHILE A < 0\n70 LET A = 0\n80 LOOP";
+        let mut interpreter = Interpreter::new();
+        interpreter.load(source).unwrap();
+
+        interpreter.run().unwrap();
+
+        assert_eq!(Some(6.0), interpreter.variable("A"));
+    }
+
+    #[test]
+    fn mismatched_loop_end_returns_error() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .load("10 WHILE A = 0\n20 FOR I = 1 TO 2\n30 WEND")
+            .unwrap();
+
+        let actual = interpreter.run();
+
+        assert_eq!(
+            Err(Error::Runtime(evaluator::Error::WendWithoutWhile.into())),
+            actual
+        );
+    }
+
+    #[test]
+    fn next_inside_while_does_not_close_outer_for() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .load("10 FOR I = 1 TO 2\n20 WHILE A = 0\n30 NEXT I")
+            .unwrap();
+
+        let actual = interpreter.run();
+
+        assert_eq!(
+            Err(Error::Runtime(evaluator::Error::NextWithoutFor.into())),
+            actual
+        );
+    }
+
+    #[test]
+    fn do_without_loop_returns_error() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load("10 DO UNTIL 1 = 1\n20 PRINT 1").unwrap();
+
+        let actual = interpreter.run();
+
+        assert_eq!(
+            Err(Error::Runtime(evaluator::Error::DoWithoutLoop.into())),
+            actual
+        );
+    }
+
+    #[test]
+    fn statements_separated_by_colons_run_in_order() {
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+
+        interpreter
+            .execute("LET A = 1: PRINT A: LET A = A + 1")
+            .unwrap();
+
+        assert_eq!("1 \n", interpreter.console().output());
+        assert_eq!(Some(2.0), interpreter.variable("A"));
+    }
+
+    #[test]
+    fn return_resumes_after_gosub_on_same_line() {
+        let source = "10 GOSUB 100: PRINT 2: END\n100 PRINT 1: RETURN";
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+        interpreter.load(source).unwrap();
+
+        interpreter.run().unwrap();
+
+        assert_eq!("1 \n2 \n", interpreter.console().output());
+    }
+
+    #[test]
+    fn if_guards_every_statement_after_then() {
+        let source = "10 IF 1 = 2 THEN PRINT 1: PRINT 2\n20 IF 1 = 1 THEN GOSUB 100: PRINT 3\n30 END\n100 PRINT 4: RETURN";
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+        interpreter.load(source).unwrap();
+
+        interpreter.run().unwrap();
+
+        assert_eq!("4 \n3 \n", interpreter.console().output());
+    }
+
+    #[test]
+    fn loops_run_within_a_single_line() {
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+
+        interpreter
+            .execute("FOR I = 1 TO 2: FOR J = 1 TO 2: PRINT I * 10 + J: NEXT J: NEXT I")
+            .unwrap();
+        interpreter
+            .execute("WHILE A < 2: LET A = A + 1: WEND: PRINT A")
+            .unwrap();
+
+        assert_eq!("11 \n12 \n21 \n22 \n2 \n", interpreter.console().output());
+    }
+
+    #[test]
+    fn direct_goto_runs_stored_program() {
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+        interpreter.load("10 PRINT 1\n20 PRINT 2").unwrap();
+
+        interpreter.execute("GOTO 20").unwrap();
+
+        assert_eq!("2 \n", interpreter.console().output());
+    }
+
+    #[test]
+    fn comments_are_listed_verbatim_and_skipped() {
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+        interpreter.execute("10 REM  Counts: 1, 2").unwrap();
+        interpreter.execute("20 PRINT 1 ' first").unwrap();
+        interpreter.execute("30 '").unwrap();
+
+        interpreter.execute("RUN").unwrap();
+        interpreter.execute("LIST").unwrap();
+
+        assert_eq!(
+            "1 \n10 REM  Counts: 1, 2\n20 PRINT 1 ' first\n30 '\n",
+            interpreter.console().output()
+        );
+    }
+
+    #[test]
+    fn if_runs_else_branch_when_condition_fails() {
+        let source = "10 FOR I = 1 TO 2: IF I = 1 THEN PRINT 1: PRINT 2 ELSE PRINT 3\n20 NEXT I";
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+        interpreter.load(source).unwrap();
+
+        interpreter.run().unwrap();
+
+        assert_eq!("1 \n2 \n3 \n", interpreter.console().output());
+    }
+
+    #[test]
+    fn if_with_line_numbers_jumps_to_branch_line() {
+        let source =
+            "10 IF A > 3 THEN 100 ELSE 200\n100 PRINT 100: END\n200 PRINT 200: LET A = 4: GOTO 10";
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+        interpreter.load(source).unwrap();
+
+        interpreter.run().unwrap();
+
+        assert_eq!("200 \n100 \n", interpreter.console().output());
+    }
+
+    #[test]
+    fn boolean_conditions_short_circuit() {
+        let source = "10 IF B <> 0 AND A / B > 1 THEN PRINT 1\n20 IF B = 0 OR A / B > 1 THEN PRINT 2\n30 IF NOT (A = 0 OR B = 0) THEN PRINT 3";
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+        interpreter.load(source).unwrap();
+
+        interpreter.run().unwrap();
+
+        assert_eq!("2 \n", interpreter.console().output());
+    }
+
+    #[test]
+    fn comparisons_evaluate_to_truth_values() {
+        let source = "10 LET A = 1: LET B = 2\n20 LET F = A < B\n30 PRINT F, A > B, (A < B) + (B < 3)\n40 IF F THEN PRINT NOT F";
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+        interpreter.load(source).unwrap();
+
+        interpreter.run().unwrap();
+
+        assert_eq!(Some(-1.0), interpreter.variable("F"));
+        assert_eq!("-1 0 -2 \n0 \n", interpreter.console().output());
+    }
+
+    #[test]
+    fn integer_operators_follow_precedence() {
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+
+        interpreter
+            .execute("PRINT 2 ^ 3 ^ 2, -2 ^ 2, 7 \\ 2, 17 MOD 5 * 2, -7 MOD 3, 2 ^ (-1), 2 ^ -1")
+            .unwrap();
+        interpreter
+            .execute("PRINT 7 \\ 2 * 2, 10 MOD 3 * 2, 10 MOD 7 \\ 2, 8 \\ 2 MOD 3, 3 * 4 MOD 5")
+            .unwrap();
+
+        assert_eq!(
+            "512 -4 3 7 -1 .5 .5 \n1 4 1 1 2 \n",
+            interpreter.console().output()
+        );
+    }
+
+    #[test]
+    fn dim_arrays_store_elements() {
+        let source = "10 DIM A(3), M(2, 2)\n20 FOR I = 0 TO 3: LET A(I) = I * I: NEXT\n30 LET M(1, 2) = A(3) + 1\n40 PRINT A(0), A(2), M(1, 2), M(2, 1)";
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+        interpreter.load(source).unwrap();
+
+        interpreter.run().unwrap();
+
+        assert_eq!("0 4 10 0 \n", interpreter.console().output());
+    }
+
+    #[test]
+    fn arrays_and_variables_do_not_share_names() {
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+
+        interpreter
+            .execute("LET A = 5: DIM A(1): LET A(1) = 7: PRINT A, A(1)")
+            .unwrap();
+
+        assert_eq!("5 7 \n", interpreter.console().output());
+    }
+
+    #[test]
+    fn array_errors_are_returned() {
+        let mut interpreter = Interpreter::new();
+        interpreter.execute("DIM A(3), M(2, 2)").unwrap();
+
+        for (code, expected) in [
+            (
+                "PRINT B(1)",
+                evaluator::Error::UndimensionedArray("B".to_string()),
+            ),
+            (
+                "LET B(1) = 1",
+                evaluator::Error::UndimensionedArray("B".to_string()),
+            ),
+            ("PRINT A(4)", evaluator::Error::SubscriptOutOfRange),
+            ("LET A(-1) = 1", evaluator::Error::SubscriptOutOfRange),
+            ("PRINT M(0, 3)", evaluator::Error::SubscriptOutOfRange),
+            (
+                "PRINT M(1)",
+                evaluator::Error::WrongNumberOfSubscripts {
+                    expected: 2,
+                    found: 1,
+                },
+            ),
+            ("DIM C(-1)", evaluator::Error::IllegalArraySize),
+            ("DIM C(300, 300)", evaluator::Error::IllegalArraySize),
+        ] {
+            let actual = interpreter.execute(code);
+
+            assert_eq!(Err(Error::Runtime(expected.into())), actual, "{code}");
+        }
+    }
+
+    #[test]
+    fn string_variables_concatenate_and_compare() {
+        let source = "10 LET A$ = \"HELLO\"\n20 LET B$ = A$ + \", \" + \"WORLD\"\n30 IF A$ < \"WORLD\" AND B$ <> A$ THEN PRINT B$\n40 IF \"B\" > \"AB\" THEN PRINT \"BYTEWISE\"";
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+        interpreter.load(source).unwrap();
+
+        interpreter.run().unwrap();
+
+        assert_eq!(
+            Some(&b"HELLO, WORLD"[..]),
+            interpreter.string_variable("B$")
+        );
+        assert_eq!("HELLO, WORLD \nBYTEWISE \n", interpreter.console().output());
+    }
+
+    #[test]
+    fn input_reads_strings_and_numbers() {
+        let console = BufferConsole::with_input(["Jane Doe", "apple, 3"]);
+        let mut interpreter = Interpreter::with_console(console);
+
+        interpreter.execute("INPUT N$: INPUT F$, C").unwrap();
+
+        assert_eq!(Some(&b"Jane Doe"[..]), interpreter.string_variable("n$"));
+        assert_eq!(Some(&b"apple"[..]), interpreter.string_variable("F$"));
+        assert_eq!(Some(3.0), interpreter.variable("C"));
+        assert_eq!(None, interpreter.string_variable("C"));
+    }
+
+    #[test]
+    fn string_arrays_store_strings() {
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+
+        interpreter
+            .execute("DIM A$(2): LET A$(1) = \"X\": PRINT A$(1) + A$(2) + \"Y\"")
+            .unwrap();
+
+        assert_eq!("XY \n", interpreter.console().output());
+    }
+
+    #[test]
+    fn mixing_strings_and_numbers_returns_type_mismatch() {
+        let mut interpreter = Interpreter::new();
+
+        for code in [
+            "LET A$ = 1",
+            "LET A = \"1\"",
+            "PRINT \"A\" + 1",
+            "PRINT \"A\" * \"B\"",
+            "PRINT -\"A\"",
+            "IF \"A\" = 1 THEN END",
+            "IF \"A\" THEN END",
+            "GOTO \"10\"",
+            "DIM A$(1): LET A$(0) = 0",
+        ] {
+            let actual = interpreter.execute(code);
+
+            assert_eq!(
+                Err(Error::Runtime(evaluator::Error::TypeMismatch.into())),
+                actual,
+                "{code}"
+            );
+        }
+    }
+
+    #[test]
+    fn floats_promote_integers_and_print_like_basic() {
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+
+        interpreter
+            .execute(
+                "LET A = 1.5: PRINT A + 2, 7 / 2, 7 / 2.0, 1 / 3.0, -.25, 2.0 * 3, 1E10, 1.5E-3",
+            )
+            .unwrap();
+        interpreter
+            .execute("PRINT 6 / 2, (2 + 3) / 2, 2 ^ -2, 100000, -40000 * 2")
+            .unwrap();
+
+        assert_eq!(
+            "3.5 3.5 3.5 .333333333 -.25 6 1E+10 1.5E-03 \n3 2.5 .25 100000 -80000 \n",
+            interpreter.console().output()
+        );
+        assert_eq!(Some(&Value::Float(1.5)), interpreter.value("A"));
+        assert_eq!(Some(1.5), interpreter.variable("A"));
+    }
+
+    #[test]
+    fn floats_work_in_loops_comparisons_and_input() {
+        let console = BufferConsole::with_input(["2.5"]);
+        let mut interpreter = Interpreter::with_console(console);
+
+        interpreter
+            .execute("INPUT X: FOR I = 0 TO 1 STEP X / 10: PRINT I, I < X: NEXT: PRINT 7.9 \\ 2, 7.5 MOD 2")
+            .unwrap();
+        interpreter
+            .execute("LET Z = 0 * (-1.5): PRINT Z = 0, Z < 0, -Z = 0.0, SGN(Z): IF Z THEN PRINT 1")
+            .unwrap();
+
+        assert_eq!(
+            "0 -1 \n.25 -1 \n.5 -1 \n.75 -1 \n1 -1 \n3 1 \n-1 0 -1 0 \n",
+            interpreter.console().output()
+        );
+        assert!(interpreter.variable("Z").unwrap().is_sign_negative());
+    }
+
+    #[test]
+    fn float_errors_are_returned() {
+        let mut interpreter = Interpreter::new();
+
+        for (code, expected) in [
+            ("PRINT 1 / 0.0", evaluator::Error::DivisionByZero),
+            ("PRINT 0.0 ^ (-1)", evaluator::Error::DivisionByZero),
+            ("PRINT 0 ^ -1", evaluator::Error::DivisionByZero),
+            ("PRINT 1E300 * 1E300", evaluator::Error::Overflow),
+            ("PRINT 40000.0 \\ 2", evaluator::Error::Overflow),
+            ("PRINT \"A\" + 1.5", evaluator::Error::TypeMismatch),
+        ] {
+            let actual = interpreter.execute(code);
+
+            assert_eq!(Err(Error::Runtime(expected.into())), actual, "{code}");
+        }
+    }
+
+    #[test]
+    fn numeric_functions_return_values() {
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+
+        interpreter
+            .execute(
+                "PRINT ABS(-3), ABS(-1.5), SGN(-2), SGN(0), SGN(.5), INT(2.7), INT(-2.5), SQR(16)",
+            )
+            .unwrap();
+        interpreter
+            .execute("PRINT SIN(0), COS(0), ATN(1) * 4, EXP(1), LOG(EXP(2)), INT(SQR(2) * 1000)")
+            .unwrap();
+
+        assert_eq!(
+            "3 1.5 -1 0 1 2 -3 4 \n0 1 3.14159265 2.71828183 2 1414 \n",
+            interpreter.console().output()
+        );
+    }
+
+    #[test]
+    fn numeric_function_errors_are_returned() {
+        let mut interpreter = Interpreter::new();
+
+        for (code, expected) in [
+            (
+                "PRINT SQR(-1)",
+                evaluator::Error::IllegalFunctionCall(Function::Sqr),
+            ),
+            (
+                "PRINT LOG(0)",
+                evaluator::Error::IllegalFunctionCall(Function::Log),
+            ),
+            ("PRINT EXP(1000)", evaluator::Error::Overflow),
+            ("PRINT SIN(\"A\")", evaluator::Error::TypeMismatch),
+        ] {
+            let actual = interpreter.execute(code);
+
+            assert_eq!(Err(Error::Runtime(expected.into())), actual, "{code}");
+        }
+        assert_eq!(
+            "?SYNTAX ERROR",
+            interpreter.execute("PRINT ABS()").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn string_functions_return_values() {
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+
+        interpreter
+            .execute("LET A$ = \"HELLO\": PRINT LEN(A$), LEFT$(A$, 2), RIGHT$(A$, 3), MID$(A$, 2, 3), MID$(A$, 4)")
+            .unwrap();
+        interpreter
+            .execute("PRINT CHR$(65) + CHR$(66), ASC(\"A\"), STR$(-1.5) + STR$(7), VAL(\" 12 APPLES\") + 1, VAL(\"-2.5E1X\")")
+            .unwrap();
+        interpreter
+            .execute("PRINT INSTR(A$, \"L\"), INSTR(4, A$, \"L\"), INSTR(A$, \"X\"), INSTR(A$, \"\"), INSTR(3, A$, \"\")")
+            .unwrap();
+
+        assert_eq!(
+            "5 HE LLO ELL LO \nAB 65 -1.57 13 -25 \n3 4 0 1 3 \n",
+            interpreter.console().output()
+        );
+    }
+
+    #[test]
+    fn string_functions_handle_out_of_range_arguments() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter
+            .execute("LET A$ = \"ABC\": LET B$ = MID$(A$, 4): LET C$ = MID$(A$, 9, 2): LET D$ = MID$(A$, 2, 99)")
+            .unwrap();
+        interpreter
+            .execute("LET E$ = LEFT$(A$, 9) + RIGHT$(A$, 0): LET F$ = MID$(A$, 1, 0): LET N = INSTR(9, A$, \"\")")
+            .unwrap();
+        interpreter
+            .execute("LET V = VAL(\"\") + VAL(\"ABC\") + VAL(\"-\") + VAL(\".\"): LET W$ = RIGHT$(\"\", 2)")
+            .unwrap();
+
+        assert_eq!(Some(&b""[..]), interpreter.string_variable("B$"));
+        assert_eq!(Some(&b""[..]), interpreter.string_variable("C$"));
+        assert_eq!(Some(&b"BC"[..]), interpreter.string_variable("D$"));
+        assert_eq!(Some(&b"ABC"[..]), interpreter.string_variable("E$"));
+        assert_eq!(Some(&b""[..]), interpreter.string_variable("F$"));
+        assert_eq!(Some(0.0), interpreter.variable("N"));
+        assert_eq!(Some(0.0), interpreter.variable("V"));
+        assert_eq!(Some(&b""[..]), interpreter.string_variable("W$"));
+    }
+
+    #[test]
+    fn string_functions_count_bytes() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter
+            .execute("LET A$ = \"\u{e9}t\u{e9}\": LET N = LEN(A$): LET B$ = LEFT$(A$, 1): LET C$ = MID$(A$, 3, 1)")
+            .unwrap();
+        interpreter
+            .execute("LET D = ASC(A$): LET E$ = CHR$(255): LET I = INSTR(A$, \"t\")")
+            .unwrap();
+
+        assert_eq!(Some(5.0), interpreter.variable("N"));
+        assert_eq!(Some(&[0xc3][..]), interpreter.string_variable("B$"));
+        assert_eq!(Some(&b"t"[..]), interpreter.string_variable("C$"));
+        assert_eq!(Some(195.0), interpreter.variable("D"));
+        assert_eq!(Some(&[0xff][..]), interpreter.string_variable("E$"));
+        assert_eq!(Some(3.0), interpreter.variable("I"));
+    }
+
+    #[test]
+    fn string_function_errors_are_returned() {
+        let mut interpreter = Interpreter::new();
+
+        for (code, expected) in [
+            (
+                "PRINT MID$(\"ABC\", 0)",
+                evaluator::Error::IllegalFunctionCall(Function::Mid),
+            ),
+            (
+                "PRINT MID$(\"ABC\", 1, -1)",
+                evaluator::Error::IllegalFunctionCall(Function::Mid),
+            ),
+            (
+                "PRINT LEFT$(\"ABC\", -1)",
+                evaluator::Error::IllegalFunctionCall(Function::Left),
+            ),
+            (
+                "PRINT CHR$(256)",
+                evaluator::Error::IllegalFunctionCall(Function::Chr),
+            ),
+            (
+                "PRINT ASC(\"\")",
+                evaluator::Error::IllegalFunctionCall(Function::Asc),
+            ),
+            (
+                "PRINT INSTR(0, \"A\", \"A\")",
+                evaluator::Error::IllegalFunctionCall(Function::Instr),
+            ),
+            ("PRINT LEN(1)", evaluator::Error::TypeMismatch),
+            ("PRINT STR$(\"1\")", evaluator::Error::TypeMismatch),
+            ("PRINT LEFT$(\"A\", \"1\")", evaluator::Error::TypeMismatch),
+        ] {
+            let actual = interpreter.execute(code);
+
+            assert_eq!(Err(Error::Runtime(expected.into())), actual, "{code}");
+        }
+        assert_eq!(
+            "?SYNTAX ERROR",
+            interpreter
+                .execute("PRINT MID$(\"A\")")
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn division_by_zero_returns_error_with_line() {
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+        interpreter
+            .load("10 LET A = 1\n20 PRINT A / (A - 1)\n30 PRINT 1")
+            .unwrap();
+
+        let actual = interpreter.run();
+
+        assert_eq!(
+            Err(Error::Runtime(evaluator::Error::DivisionByZero.into())),
+            actual
+        );
+        assert_eq!(Some(20), interpreter.current_line());
+        assert_eq!("", interpreter.console().output());
+    }
+
+    #[test]
+    fn runtime_error_spans_failing_operation_of_stored_line() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .load("10 LET A = 0\n20 LET B = 1: PRINT B / A")
+            .unwrap();
+
+        let error = interpreter.run().unwrap_err();
+
+        let source = interpreter.line_source(20).unwrap();
+        assert_eq!(
+            "20 LET B = 1: PRINT B / A\n                      ^",
+            error.span().underline(source)
+        );
+    }
+
+    #[test]
+    fn runtime_error_spans_failing_factor_or_statement() {
+        let mut interpreter = Interpreter::new();
+
+        for (code, expected) in [
+            ("PRINT 1 + SQR(-(2 + 3)) * 2", "          ^^^^^^^^^^^^^"),
+            ("PRINT 1 + (40000 \\ 2)", "                 ^"),
+            ("PRINT (1 + 2) MOD (1 - 1)", "              ^^^"),
+            ("LET A$ = 1", "^^^^^^^^^^"),
+        ] {
+            let error = interpreter.execute(code).unwrap_err();
+
+            assert_eq!(
+                format!("{code}\n{expected}"),
+                error.span().underline(code),
+                "{code}"
+            );
+        }
+    }
+
+    #[test]
+    fn integer_overflow_promotes_to_float() {
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+
+        interpreter
+            .execute("LET B = 200 * 200: LET A = 0 - 32767 - 1: PRINT B, 32767 + 1, -32767 - 2")
+            .unwrap();
+        interpreter
+            .execute("PRINT 2 ^ 15, -A, ABS(A), A: FOR I = 32766 TO 32767: NEXT: PRINT I")
+            .unwrap();
+
+        assert_eq!(
+            "40000 32768 -32769 \n32768 32768 32768 -32768 \n32768 \n",
+            interpreter.console().output()
+        );
+        assert_eq!(Some(&Value::Float(40000.0)), interpreter.value("B"));
+        assert_eq!(Some(&Value::Integer(-32768)), interpreter.value("A"));
+    }
+
+    #[test]
+    fn arithmetic_overflow_returns_error() {
+        let mut interpreter = Interpreter::new();
+
+        for code in [
+            "LET A = 0 - 32767 - 1: PRINT A \\ (-1)",
+            "PRINT 40000 MOD 3",
+            "PRINT 1E300 * 1E300",
+            "LET A = 40000: GOTO A",
+        ] {
+            let actual = interpreter.execute(code);
+
+            assert_eq!(
+                Err(Error::Runtime(evaluator::Error::Overflow.into())),
+                actual,
+                "{code}"
+            );
+        }
+        assert_eq!(
+            Err(Error::Runtime(evaluator::Error::DivisionByZero.into())),
+            interpreter.execute("PRINT 5 MOD 0")
+        );
+    }
+
+    #[test]
+    fn errors_display_classic_message_and_cause() {
+        use std::error::Error as _;
+
+        let mut interpreter = Interpreter::new();
+        interpreter.load("10 GOTO 120").unwrap();
+
+        let runtime = interpreter.run().unwrap_err();
+        let syntax = interpreter.execute("40 PRINT (").unwrap_err();
+
+        assert_eq!("?UNDEFINED LINE 120", runtime.to_string());
+        assert_eq!(
+            "there is no line 120",
+            runtime.source().unwrap().to_string()
+        );
+        assert_eq!("?SYNTAX ERROR", syntax.to_string());
+        assert_eq!(
+            "line ended unexpectedly",
+            syntax.source().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn end_stops_program() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .load("10 LET A = 1\n20 END\n30 LET A = 2")
+            .unwrap();
+
+        let actual = interpreter.run();
+
+        assert_eq!(Ok(Outcome::Stopped), actual);
+        assert_eq!(Some(1.0), interpreter.variable("A"));
+    }
+
+    #[test]
+    fn run_without_end_finishes_program() {
+        let mut interpreter = Interpreter::new();
+        interpreter.execute("10 LET A = 1").unwrap();
+
+        let actual = interpreter.execute("RUN");
+
+        assert_eq!(Ok(Outcome::Finished), actual);
+    }
+
+    #[test]
+    fn run_inside_program_starts_over_without_recursing() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .load("10 LET N = N + 1\n20 IF N < 5000 THEN RUN")
+            .unwrap();
+
+        let actual = interpreter.run();
+
+        assert_eq!(Ok(Outcome::Finished), actual);
+        assert_eq!(Some(5000.0), interpreter.variable("N"));
+    }
+
+    #[test]
+    fn step_over_run_moves_to_first_line() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load("10 LET N = N + 1\n20 RUN").unwrap();
+
+        assert_eq!(Ok(None), interpreter.step());
+        assert_eq!(Ok(None), interpreter.step());
+        assert_eq!(Some(1.0), interpreter.variable("N"));
+        assert_eq!(Ok(None), interpreter.step());
+        assert_eq!(Some(2.0), interpreter.variable("N"));
+    }
+
+    #[test]
+    fn bye_requests_exit() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load("10 IF 1 = 1 THEN BYE").unwrap();
+
+        assert_eq!(Ok(Outcome::Exit), interpreter.run());
+        assert_eq!(Ok(Outcome::Exit), interpreter.execute("SYSTEM"));
+    }
+
+    #[test]
+    fn load_unnumbered_line_returns_error() {
+        let mut interpreter = Interpreter::new();
+
+        let actual = interpreter.load("PRINT 1");
+
+        assert_eq!(
+            Err(vec![LoadError::new(
+                1,
+                Error::Runtime(evaluator::Error::MissingLineNumber.into())
+            )]),
+            actual
+        );
+    }
+
+    #[test]
+    fn load_skips_shebang_line() {
+        let source = "#!/usr/bin/env rusty-basic\n10 LET A = 1\n";
+        let mut interpreter = Interpreter::new();
+
+        interpreter.load(source).unwrap();
+        interpreter.run().unwrap();
+
+        assert_eq!(Some(1.0), interpreter.variable("A"));
+    }
+
+    #[test]
+    fn load_reports_every_invalid_line() {
+        let source = "10 PRINT @\n20 LET A = 1\nPRINT 2\n30 GOTO\n";
+        let mut interpreter = Interpreter::new();
+
+        let actual = interpreter.load(source);
+
+        let lines: Vec<usize> = actual.unwrap_err().iter().map(LoadError::line).collect();
+        assert_eq!(vec![1, 3, 4], lines);
+        assert_eq!(None, interpreter.line_source(20));
+    }
+
+    #[test]
+    fn parse_program_keeps_valid_lines_and_diagnostics() {
+        let source = "#!/usr/bin/env rusty-basic\n10 LET A = 1\n20 PRINT (\n\n30 END 5\n40 END";
+
+        let actual = parse_program(source);
+
+        let lines: Vec<(usize, Option<u16>)> = actual
+            .lines()
+            .iter()
+            .map(|(index, line)| (*index, line.number()))
+            .collect();
+        assert_eq!(vec![(2, Some(10)), (6, Some(40))], lines);
+        assert_eq!(
+            vec![
+                LoadError::new(3, Error::Parser(parser::Error::NoMoreToken.into())),
+                LoadError::new(
+                    5,
+                    Error::Parser(
+                        parser::Error::TrailingToken {
+                            found: Token::NumberLiteral(5)
+                        }
+                        .into()
+                    )
+                ),
+            ],
+            actual.diagnostics()
+        );
+    }
+
+    #[test]
+    fn load_invalid_line_returns_source_line() {
+        let source = "10 LET A = 1\n\n30 LET = 2\n";
+        let mut interpreter = Interpreter::new();
+
+        let actual = interpreter.load(source);
+
+        assert_eq!(
+            Err(vec![LoadError::new(
+                3,
+                Error::Parser(parser::Error::VariableNotFound.into())
+            )]),
+            actual
+        );
+    }
+
+    #[test]
+    fn runtime_error_keeps_current_line() {
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+        interpreter.load("10 LET A = 1\n20 INPUT A\n").unwrap();
+
+        let actual = interpreter.run();
+
+        assert_eq!(
+            Err(Error::Runtime(
+                evaluator::Error::Io(std::io::ErrorKind::UnexpectedEof).into()
+            )),
+            actual
+        );
+        assert_eq!(Some(20), interpreter.current_line());
+    }
+
+    #[test]
+    fn variable_with_invalid_name_returns_none() {
+        let interpreter = Interpreter::new();
+
+        assert_eq!(None, interpreter.variable("A B"));
+        assert_eq!(None, interpreter.variable("1"));
+        assert_eq!(None, interpreter.variable("PRINT"));
+        assert_eq!(None, interpreter.variable("A$"));
+    }
+
+    #[test]
+    fn long_variable_names_are_distinct() {
+        let source = "10 LET COUNT = 3: LET COUNTER = 4: LET TOTAL2 = COUNT * COUNTER\n20 LET NAME$ = \"BOB\"\n30 FOR INDEX = 1 TO 2: LET SUM = SUM + INDEX: NEXT INDEX";
+        let mut interpreter = Interpreter::new();
+        interpreter.load(source).unwrap();
+
+        interpreter.run().unwrap();
+
+        assert_eq!(Some(12.0), interpreter.variable("total2"));
+        assert_eq!(Some(3.0), interpreter.variable("SUM"));
+        assert_eq!(Some(0.0), interpreter.variable("UNUSED"));
+        assert_eq!(Some(&b"BOB"[..]), interpreter.string_variable("NAME$"));
+        assert_eq!(Some(0.0), interpreter.variable("NAME"));
+    }
+
+    #[test]
+    fn print_writes_to_console() {
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+
+        interpreter.execute("PRINT \"A\", 1 + 2").unwrap();
+
+        assert_eq!("A 3 \n", interpreter.console().output());
+    }
+
+    #[test]
+    fn input_reads_from_console() {
+        let console = BufferConsole::with_input(["3 4"]);
+        let mut interpreter = Interpreter::with_console(console);
+
+        interpreter.execute("INPUT A, B").unwrap();
+
+        assert_eq!(Some(3.0), interpreter.variable("A"));
+        assert_eq!(Some(4.0), interpreter.variable("B"));
+    }
+
+    #[test]
+    fn input_asks_again_until_input_matches_variables() {
+        let console = BufferConsole::with_input(["X", "1, 2, 3", "X, Y", "5, ABC"]);
+        let mut interpreter = Interpreter::with_console(console);
+
+        interpreter.execute("INPUT A, B$").unwrap();
+
+        assert_eq!(
+            "?REDO FROM START\n?REDO FROM START\n?REDO FROM START\n",
+            interpreter.console().output()
+        );
+        assert_eq!(Some(5.0), interpreter.variable("A"));
+        assert_eq!(Some(&b"ABC"[..]), interpreter.string_variable("B$"));
+    }
+
+    #[test]
+    fn input_without_remaining_input_returns_error() {
+        let mut interpreter = Interpreter::with_console(BufferConsole::new());
+
+        let actual = interpreter.execute("INPUT A");
+
+        assert_eq!(
+            Err(Error::Runtime(
+                evaluator::Error::Io(std::io::ErrorKind::UnexpectedEof).into()
+            )),
+            actual
+        );
+    }
+}
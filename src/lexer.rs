@@ -1,22 +1,62 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::slice;
+
+use crate::ast::Function;
+use crate::span::{Span, Spanned};
 use crate::token::Token;
 
 #[derive(Debug, PartialEq)]
 pub enum Error {
     /// Found an invalid character like `b'@'`, `b'$'`
     InvalidCharacter,
-    /// Lexed identifier is not keyword or variable
-    UnknownIdentifier,
     /// Non terminated string literal like `"Hello, World!`
     NonTerminatedStringLiteral,
+    /// Numeric literal too large for a float like `1E999`, found at the 1-based `column`
+    NumberOutOfRange { text: String, column: usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidCharacter => write!(f, "invalid character"),
+            Error::NonTerminatedStringLiteral => write!(f, "string literal is not terminated"),
+            Error::NumberOutOfRange { text, column } => {
+                write!(f, "number {} at column {} is out of range", text, column)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Takes the rest of the line, without its line terminator.
+fn rest_of_line<'a>(chars: impl Iterator<Item = &'a u8>) -> Vec<u8> {
+    let mut text: Vec<u8> = chars.copied().collect();
+    while let Some(b'\r' | b'\n') = text.last() {
+        text.pop();
+    }
+
+    text
+}
+
+/// Appends the digits at the front of `chars` to `text`.
+fn take_digits(chars: &mut Peekable<slice::Iter<u8>>, text: &mut String) {
+    while let Some(&ch) = chars.next_if(|ch| ch.is_ascii_digit()) {
+        text.push(ch as char);
+    }
 }
 
-pub fn lex(code: &[u8]) -> Result<Vec<Token>, Error> {
+/// Splits `code` into tokens, each with the span of the bytes it was read from.
+pub fn lex(code: &[u8]) -> Result<Vec<Spanned<Token>>, Spanned<Error>> {
     let mut tokens = Vec::new();
     let mut chars = code.iter().peekable();
 
     while let Some(ch) = chars.next() {
+        let start = code.len() - chars.len() - 1;
         let token = match ch {
             b',' => Token::Comma,
+            b':' => Token::Colon,
             b'(' => Token::OpeningParenthesis,
             b')' => Token::ClosingParenthesis,
             b'=' => Token::Equal,
@@ -46,16 +86,63 @@ pub fn lex(code: &[u8]) -> Result<Vec<Token>, Error> {
             b'-' => Token::Minus,
             b'*' => Token::Multiply,
             b'/' => Token::Divide,
-            b'0'..=b'9' => {
-                let mut value: i16 = (ch - b'0') as i16;
-                while let Some(&ch @ b'0'..=b'9') = chars.peek() {
-                    value *= 10;
-                    value += (ch - b'0') as i16;
-                    chars.next();
+            b'\\' => Token::IntegerDivide,
+            b'^' => Token::Power,
+            b'0'..=b'9' | b'.' => {
+                let column = start + 1;
+                let mut text = String::from(*ch as char);
+                take_digits(&mut chars, &mut text);
+
+                let mut is_float = *ch == b'.';
+                if !is_float && chars.next_if_eq(&&b'.').is_some() {
+                    text.push('.');
+                    is_float = true;
+                }
+                if is_float {
+                    take_digits(&mut chars, &mut text);
+                }
+
+                // `E` starts an exponent only if digits follow, as in `1E3` or `1E-3`
+                let mut exponent = chars.clone();
+                if exponent
+                    .next_if(|ch| ch.eq_ignore_ascii_case(&b'E'))
+                    .is_some()
+                {
+                    let sign = exponent.next_if(|ch| matches!(ch, b'+' | b'-'));
+                    if exponent.peek().is_some_and(|ch| ch.is_ascii_digit()) {
+                        text.push('E');
+                        text.extend(sign.map(|&sign| sign as char));
+                        take_digits(&mut exponent, &mut text);
+                        chars = exponent;
+                        is_float = true;
+                    }
+                }
+
+                let span = Span::new(start, code.len() - chars.len());
+                if text == "." {
+                    return Err(Spanned::new(Error::InvalidCharacter, span));
                 }
 
-                Token::NumberLiteral(value)
+                // integers too large for an integer are read as floats, like `100000`
+                let token = match text.parse() {
+                    Ok(value) if !is_float => Some(Token::NumberLiteral(value)),
+                    _ => text
+                        .parse()
+                        .ok()
+                        .filter(|value: &f64| value.is_finite())
+                        .map(Token::FloatLiteral),
+                };
+
+                match token {
+                    Some(token) => token,
+                    None => {
+                        return Err(Spanned::new(Error::NumberOutOfRange { text, column }, span))
+                    }
+                }
             }
+            b'\'' => Token::Comment {
+                text: rest_of_line(&mut chars),
+            },
             b'"' => {
                 let mut value = Vec::new();
                 let mut is_string_terminated = false;
@@ -69,16 +156,19 @@ pub fn lex(code: &[u8]) -> Result<Vec<Token>, Error> {
                 }
 
                 if !is_string_terminated {
-                    return Err(Error::NonTerminatedStringLiteral);
+                    return Err(Spanned::new(
+                        Error::NonTerminatedStringLiteral,
+                        Span::new(start, code.len() - chars.len()),
+                    ));
                 }
 
                 Token::StringLiteral { value }
             }
             ch if ch.is_ascii_alphabetic() => {
-                /// The longest length of valid identifiers.
-                const MAX_IDENTIFIER_LENGTH: usize = 6;
+                /// The length of the longest keyword.
+                const MAX_KEYWORD_LENGTH: usize = 6;
 
-                let mut identifier = Vec::with_capacity(MAX_IDENTIFIER_LENGTH);
+                let mut identifier = Vec::with_capacity(MAX_KEYWORD_LENGTH);
                 identifier.push(ch.to_ascii_uppercase());
 
                 while let Some(&ch) = chars.peek() {
@@ -92,34 +182,68 @@ pub fn lex(code: &[u8]) -> Result<Vec<Token>, Error> {
 
                 debug_assert_eq!(identifier, identifier.to_ascii_uppercase());
 
-                // handle variable identifier
-                if identifier.len() == 1 {
-                    Token::Variable {
-                        identifier: identifier[0],
-                    }
-                } else {
-                    match identifier.as_slice() {
-                        b"PRINT" => Token::Print,
-                        b"IF" => Token::If,
-                        b"THEN" => Token::Then,
-                        b"GOTO" => Token::Goto,
-                        b"INPUT" => Token::Input,
-                        b"LET" => Token::Let,
-                        b"GOSUB" => Token::GoSub,
-                        b"RETURN" => Token::Return,
-                        b"CLEAR" => Token::Clear,
-                        b"LIST" => Token::List,
-                        b"RUN" => Token::Run,
-                        b"END" => Token::End,
-                        _ => return Err(Error::UnknownIdentifier),
+                match identifier.as_slice() {
+                    b"PRINT" => Token::Print,
+                    b"IF" => Token::If,
+                    b"THEN" => Token::Then,
+                    b"ELSE" => Token::Else,
+                    b"AND" => Token::And,
+                    b"OR" => Token::Or,
+                    b"NOT" => Token::Not,
+                    b"MOD" => Token::Mod,
+                    b"GOTO" => Token::Goto,
+                    b"INPUT" => Token::Input,
+                    b"LET" => Token::Let,
+                    b"DIM" => Token::Dim,
+                    b"GOSUB" => Token::GoSub,
+                    b"RETURN" => Token::Return,
+                    b"CLEAR" => Token::Clear,
+                    b"LIST" => Token::List,
+                    b"RUN" => Token::Run,
+                    b"END" => Token::End,
+                    b"BYE" | b"SYSTEM" => Token::Bye,
+                    b"FOR" => Token::For,
+                    b"TO" => Token::To,
+                    b"STEP" => Token::Step,
+                    b"NEXT" => Token::Next,
+                    b"WHILE" => Token::While,
+                    b"WEND" => Token::Wend,
+                    b"DO" => Token::Do,
+                    b"LOOP" => Token::Loop,
+                    b"UNTIL" => Token::Until,
+                    b"REM" => Token::Remark {
+                        text: rest_of_line(&mut chars),
+                    },
+                    // any other identifier names a function or a variable
+                    _ => {
+                        let mut name: String = identifier.iter().map(|&ch| ch as char).collect();
+                        let is_string = chars.next_if_eq(&&b'$').is_some();
+                        if is_string {
+                            name.push('$');
+                        }
+
+                        if let Some(function) = Function::from_name(&name) {
+                            Token::Function(function)
+                        } else if is_string {
+                            name.pop();
+                            Token::StringVariable { name }
+                        } else {
+                            Token::Variable { name }
+                        }
                     }
                 }
             }
             ch if ch.is_ascii_whitespace() => continue,
-            _ => return Err(Error::InvalidCharacter),
+            _ => {
+                return Err(Spanned::new(
+                    Error::InvalidCharacter,
+                    Span::new(start, code.len() - chars.len()),
+                ))
+            }
         };
 
-        tokens.push(token);
+        let span = Span::new(start, code.len() - chars.len());
+        tokens.push(Spanned::new(token, span));
     }
 
     Ok(tokens)
@@ -129,6 +253,12 @@ pub fn lex(code: &[u8]) -> Result<Vec<Token>, Error> {
 mod tests {
     use super::*;
 
+    fn lex_tokens(code: &[u8]) -> Result<Vec<Token>, Error> {
+        let tokens = lex(code).map_err(Spanned::into_node)?;
+
+        Ok(tokens.into_iter().map(Spanned::into_node).collect())
+    }
+
     #[test]
     fn lex_hello_world_returns_tokens() {
         let code = b"PRINT \"Hello, World!\"";
@@ -139,7 +269,7 @@ mod tests {
             },
         ];
 
-        let actual = lex(code);
+        let actual = lex_tokens(code);
 
         assert_eq!(Ok(expected), actual);
     }
@@ -159,7 +289,25 @@ mod tests {
             Token::NumberLiteral(5),
         ];
 
-        let actual = lex(expression);
+        let actual = lex_tokens(expression);
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn lex_integer_operators_returns_tokens() {
+        let expression = b"7 \\ 2 MOD 3 ^ 2";
+        let expected = vec![
+            Token::NumberLiteral(7),
+            Token::IntegerDivide,
+            Token::NumberLiteral(2),
+            Token::Mod,
+            Token::NumberLiteral(3),
+            Token::Power,
+            Token::NumberLiteral(2),
+        ];
+
+        let actual = lex_tokens(expression);
 
         assert_eq!(Ok(expected), actual);
     }
@@ -182,7 +330,118 @@ mod tests {
             Token::End,
         ];
 
-        let actual = lex(code);
+        let actual = lex_tokens(code);
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn lex_system_returns_bye_token() {
+        let code = b"BYE SYSTEM";
+        let expected = vec![Token::Bye, Token::Bye];
+
+        let actual = lex_tokens(code);
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn lex_for_loop_returns_tokens() {
+        let code = b"FOR I = 10 TO 1 STEP -1 NEXT I";
+        let expected = vec![
+            Token::For,
+            Token::Variable { name: "I".into() },
+            Token::Equal,
+            Token::NumberLiteral(10),
+            Token::To,
+            Token::NumberLiteral(1),
+            Token::Step,
+            Token::Minus,
+            Token::NumberLiteral(1),
+            Token::Next,
+            Token::Variable { name: "I".into() },
+        ];
+
+        let actual = lex_tokens(code);
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn lex_loop_keywords_returns_tokens() {
+        let code = b"WHILE WEND DO LOOP UNTIL";
+        let expected = vec![
+            Token::While,
+            Token::Wend,
+            Token::Do,
+            Token::Loop,
+            Token::Until,
+        ];
+
+        let actual = lex_tokens(code);
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn lex_statement_separator_returns_colon_token() {
+        let code = b"LET A = 1: PRINT A";
+        let expected = vec![
+            Token::Let,
+            Token::Variable { name: "A".into() },
+            Token::Equal,
+            Token::NumberLiteral(1),
+            Token::Colon,
+            Token::Print,
+            Token::Variable { name: "A".into() },
+        ];
+
+        let actual = lex_tokens(code);
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn lex_remark_keeps_rest_of_line_verbatim() {
+        let code = b"REM Hello, \"World\": 'x' @\n";
+        let expected = vec![Token::Remark {
+            text: b" Hello, \"World\": 'x' @".to_vec(),
+        }];
+
+        let actual = lex_tokens(code);
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn lex_apostrophe_comment_after_statement_returns_tokens() {
+        let code = b"PRINT 1 'one";
+        let expected = vec![
+            Token::Print,
+            Token::NumberLiteral(1),
+            Token::Comment {
+                text: b"one".to_vec(),
+            },
+        ];
+
+        let actual = lex_tokens(code);
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn lex_boolean_operators_returns_tokens() {
+        let code = b"not a and b or c";
+        let expected = vec![
+            Token::Not,
+            Token::Variable { name: "A".into() },
+            Token::And,
+            Token::Variable { name: "B".into() },
+            Token::Or,
+            Token::Variable { name: "C".into() },
+        ];
+
+        let actual = lex_tokens(code);
 
         assert_eq!(Ok(expected), actual);
     }
@@ -192,15 +451,15 @@ mod tests {
         let code = b"IF A < B THEN PRINT Z";
         let expected = vec![
             Token::If,
-            Token::Variable { identifier: b'A' },
+            Token::Variable { name: "A".into() },
             Token::LessThan,
-            Token::Variable { identifier: b'B' },
+            Token::Variable { name: "B".into() },
             Token::Then,
             Token::Print,
-            Token::Variable { identifier: b'Z' },
+            Token::Variable { name: "Z".into() },
         ];
 
-        let actual = lex(code);
+        let actual = lex_tokens(code);
 
         assert_eq!(Ok(expected), actual);
     }
@@ -210,33 +469,52 @@ mod tests {
         let code = b"IF a < b THEN PRINT z";
         let expected = vec![
             Token::If,
-            Token::Variable { identifier: b'A' },
+            Token::Variable { name: "A".into() },
             Token::LessThan,
-            Token::Variable { identifier: b'B' },
+            Token::Variable { name: "B".into() },
             Token::Then,
             Token::Print,
-            Token::Variable { identifier: b'Z' },
+            Token::Variable { name: "Z".into() },
         ];
 
-        let actual = lex(code);
+        let actual = lex_tokens(code);
 
         assert_eq!(Ok(expected), actual);
     }
 
     #[test]
-    fn lex_unknown_identifier_returns_error() {
-        let invalid_code = b"PRINT HELLO";
+    fn lex_long_identifiers_returns_variables() {
+        let code = b"LET Total2 = COUNT + printer: PRINT NAME$";
+        let expected = vec![
+            Token::Let,
+            Token::Variable {
+                name: "TOTAL2".into(),
+            },
+            Token::Equal,
+            Token::Variable {
+                name: "COUNT".into(),
+            },
+            Token::Plus,
+            Token::Variable {
+                name: "PRINTER".into(),
+            },
+            Token::Colon,
+            Token::Print,
+            Token::StringVariable {
+                name: "NAME".into(),
+            },
+        ];
 
-        let actual = lex(invalid_code);
+        let actual = lex_tokens(code);
 
-        assert_eq!(Err(Error::UnknownIdentifier), actual);
+        assert_eq!(Ok(expected), actual);
     }
 
     #[test]
     fn lex_non_terminated_string_returns_error() {
         let invalid_code = b"PRINT \"Hello, World!";
 
-        let actual = lex(invalid_code);
+        let actual = lex_tokens(invalid_code);
 
         assert_eq!(Err(Error::NonTerminatedStringLiteral), actual);
     }
@@ -251,7 +529,7 @@ mod tests {
             Token::StringLiteral { value: Vec::new() },
         ];
 
-        let actual = lex(code);
+        let actual = lex_tokens(code);
 
         assert_eq!(Ok(expected), actual);
     }
@@ -261,8 +539,171 @@ mod tests {
         let code = b"9999";
         let expected = vec![Token::NumberLiteral(9999)];
 
-        let actual = lex(code);
+        let actual = lex_tokens(code);
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn lex_number_with_largest_value_returns_tokens() {
+        let code = b"32767";
+        let expected = vec![Token::NumberLiteral(32767)];
+
+        let actual = lex_tokens(code);
 
         assert_eq!(Ok(expected), actual);
     }
+
+    #[test]
+    fn lex_integer_out_of_range_returns_float() {
+        let code = b"PRINT 1, 32768, 99999999999999999999";
+        let expected = vec![
+            Token::Print,
+            Token::NumberLiteral(1),
+            Token::Comma,
+            Token::FloatLiteral(32768.0),
+            Token::Comma,
+            Token::FloatLiteral(1E20),
+        ];
+
+        let actual = lex_tokens(code);
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn lex_function_names_returns_functions() {
+        let code = b"PRINT sqr(ABS(X)), ABSX";
+        let expected = vec![
+            Token::Print,
+            Token::Function(Function::Sqr),
+            Token::OpeningParenthesis,
+            Token::Function(Function::Abs),
+            Token::OpeningParenthesis,
+            Token::Variable { name: "X".into() },
+            Token::ClosingParenthesis,
+            Token::ClosingParenthesis,
+            Token::Comma,
+            Token::Variable {
+                name: "ABSX".into(),
+            },
+        ];
+
+        let actual = lex_tokens(code);
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn lex_string_function_names_returns_functions() {
+        let code = b"LEFT$(LEFT, 1) + MID$";
+        let expected = vec![
+            Token::Function(Function::Left),
+            Token::OpeningParenthesis,
+            Token::Variable {
+                name: "LEFT".into(),
+            },
+            Token::Comma,
+            Token::NumberLiteral(1),
+            Token::ClosingParenthesis,
+            Token::Plus,
+            Token::Function(Function::Mid),
+        ];
+
+        let actual = lex_tokens(code);
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn lex_float_literals_returns_tokens() {
+        let code = b"1.5 .25 2. 1.5E-3 2e3 7E+1 3ELSE";
+        let expected = vec![
+            Token::FloatLiteral(1.5),
+            Token::FloatLiteral(0.25),
+            Token::FloatLiteral(2.0),
+            Token::FloatLiteral(0.0015),
+            Token::FloatLiteral(2000.0),
+            Token::FloatLiteral(70.0),
+            Token::NumberLiteral(3),
+            Token::Else,
+        ];
+
+        let actual = lex_tokens(code);
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn lex_float_out_of_range_returns_error() {
+        let code = b"PRINT 1E999";
+        let expected = Error::NumberOutOfRange {
+            text: String::from("1E999"),
+            column: 7,
+        };
+
+        let actual = lex_tokens(code);
+
+        assert_eq!(Err(expected), actual);
+    }
+
+    #[test]
+    fn lex_number_with_many_digits_returns_error() {
+        let text = "9".repeat(400);
+        let expected = Error::NumberOutOfRange {
+            text: text.clone(),
+            column: 1,
+        };
+
+        let actual = lex_tokens(text.as_bytes());
+
+        assert_eq!(Err(expected), actual);
+    }
+
+    #[test]
+    fn lex_returns_spans_of_tokens() {
+        let code = b"10 PRINT \"A\", X";
+        let expected = vec![
+            Span::new(0, 2),
+            Span::new(3, 8),
+            Span::new(9, 12),
+            Span::new(12, 13),
+            Span::new(14, 15),
+        ];
+
+        let actual = lex(code).unwrap();
+
+        let spans: Vec<Span> = actual.iter().map(Spanned::span).collect();
+        assert_eq!(expected, spans);
+    }
+
+    #[test]
+    fn lex_string_variables_returns_tokens() {
+        let code = b"LET A$ = b$ + \"!\"";
+        let expected = vec![
+            Token::Let,
+            Token::StringVariable { name: "A".into() },
+            Token::Equal,
+            Token::StringVariable { name: "B".into() },
+            Token::Plus,
+            Token::StringLiteral {
+                value: b"!".to_vec(),
+            },
+        ];
+
+        let actual = lex_tokens(code);
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn lex_invalid_character_returns_error_with_span() {
+        let code = b"PRINT @";
+
+        let actual = lex(code);
+
+        let error = actual.unwrap_err();
+        assert_eq!(&Error::InvalidCharacter, error.node());
+        assert_eq!(Span::new(6, 7), error.span());
+    }
 }
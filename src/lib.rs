@@ -0,0 +1,12 @@
+pub mod ast;
+pub mod console;
+pub mod evaluator;
+pub mod interpreter;
+pub mod lexer;
+pub mod parser;
+mod program;
+pub mod span;
+pub mod token;
+
+pub use evaluator::{Outcome, Value};
+pub use interpreter::{parse_program, Error, Interpreter, LoadError, ParsedProgram};
@@ -1,51 +1,123 @@
-mod ast;
-mod evaluator;
-mod lexer;
-mod parser;
-mod token;
-
-use std::collections::VecDeque;
+use std::env;
+use std::error::Error as _;
+use std::fs;
 use std::io::{stdin, stdout, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+use rusty_basic::console::Console;
+use rusty_basic::{Error, Interpreter, Outcome};
 
-use evaluator::Evaluator;
-use lexer::lex;
-use parser::Parser;
+fn main() -> ExitCode {
+    let mut verbose = false;
+    let mut path = None;
+    for argument in env::args_os().skip(1) {
+        if argument == "-v" || argument == "--verbose" {
+            verbose = true;
+        } else {
+            path = Some(argument);
+        }
+    }
+
+    match path {
+        Some(path) => run_file(Path::new(&path), verbose),
+        None => {
+            run_prompt(verbose);
+            ExitCode::SUCCESS
+        }
+    }
+}
 
-fn main() {
-    let mut evaluator = Evaluator::new();
+fn run_prompt(verbose: bool) {
+    let mut interpreter = Interpreter::new();
 
     const VERSION: &str = env!("CARGO_PKG_VERSION");
     println!("RustyBASIC - TinyBASIC written in Rust.");
     println!("Ver {VERSION}");
-    println!("Type 'end' to quit program.");
+    println!("Type 'bye' to quit program.");
 
     loop {
         print!("> ");
         stdout().flush().unwrap();
 
         let mut buffer = String::new();
-        stdin().read_line(&mut buffer).unwrap();
-        let tokens = match lex(buffer.as_bytes()) {
-            Ok(tokens) => tokens,
-            Err(error) => {
-                eprintln!("Lexer error: {error:?}");
-                continue;
-            }
-        };
-
-        let mut parser = Parser::new(VecDeque::from(tokens));
-        let line = match parser.parse_line() {
-            Ok(line) => line,
-            Err(error) => {
-                eprintln!("Parser error: {error:?}");
-                continue;
-            }
-        };
-
-        let result = evaluator.process_line(line);
-        if let Err(error) = result {
-            eprintln!("Runtime error: {error:?}");
-            continue;
+        if stdin().read_line(&mut buffer).unwrap() == 0 {
+            break;
+        }
+
+        match interpreter.execute(&buffer) {
+            Ok(Outcome::Exit) => break,
+            Ok(Outcome::Finished | Outcome::Stopped) => {}
+            Err(error) => eprintln!("{}", report(&interpreter, &error, &buffer, verbose)),
+        }
+    }
+}
+
+fn run_file(path: &Path, verbose: bool) -> ExitCode {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("{}: {error}", path.display());
+            return ExitCode::FAILURE;
         }
+    };
+
+    let mut interpreter = Interpreter::new();
+    if let Err(errors) = interpreter.load(&source) {
+        for error in errors {
+            let code = source.lines().nth(error.line() - 1).unwrap_or_default();
+            let message = report(&interpreter, error.error(), code, verbose);
+            eprintln!("{}:{}: {message}", path.display(), error.line());
+        }
+        return ExitCode::FAILURE;
+    }
+
+    match interpreter.run() {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(error) => {
+            let message = report(&interpreter, &error, "", verbose);
+            eprintln!("{}: {message}", path.display());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Describes `error` like a classic BASIC, followed by the line it refers to and a caret under
+/// the offending part. In verbose mode the cause of the error is explained too.
+///
+/// Runtime errors raised in a stored line refer to its source, the others to `code`.
+fn report<C: Console>(
+    interpreter: &Interpreter<C>,
+    error: &Error,
+    code: &str,
+    verbose: bool,
+) -> String {
+    let line = match error {
+        Error::Runtime(_) => interpreter.current_line(),
+        _ => line_number(code),
+    };
+    let source = match error {
+        Error::Runtime(_) => line.and_then(|line| interpreter.line_source(line)),
+        _ => None,
+    }
+    .unwrap_or(code);
+
+    let mut message = error.to_string();
+    if let Some(line) = line {
+        message += &format!(" IN {line}");
+    }
+    if let (true, Some(cause)) = (verbose, error.source()) {
+        message += &format!(": {cause}");
     }
+
+    format!("{message}\n{}", error.span().underline(source))
+}
+
+/// Returns the line number `code` starts with, if any.
+fn line_number(code: &str) -> Option<u16> {
+    let code = code.trim_start();
+    let digits = code
+        .find(|ch: char| !ch.is_ascii_digit())
+        .unwrap_or(code.len());
+    code[..digits].parse().ok()
 }
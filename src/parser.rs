@@ -1,53 +1,125 @@
 use std::collections::VecDeque;
+use std::fmt;
 
 use crate::ast::{
-    AdditiveOperator, Expression, ExpressionListElement, Factor, Line, MultiplicativeOperator,
-    NumberLiteral, RelationalOperator, Statement, StringLiteral, Term, Variable,
+    AdditiveOperator, ArithmeticExpression, ArrayElement, Branch, Expression, Factor, FloatLiteral,
+    Function, FunctionCall, Line, LoopCondition, MultiplicativeOperator, NumberLiteral,
+    RelationalOperator, Statement, StringLiteral, Target, Term, Variable, MAX_LINE_NUMBER,
 };
+use crate::span::{Span, Spanned};
 use crate::token::Token;
 
 #[derive(Debug, PartialEq)]
 pub enum Error {
-    UnexpectedToken { expected: Token, found: Token },
+    UnexpectedToken {
+        expected: Token,
+        found: Token,
+    },
+    /// A statement is followed by `found` instead of a colon or the end of the line.
+    TrailingToken {
+        found: Token,
+    },
     VariableNotFound,
+    /// `function` is called with `found` arguments, which it does not accept.
+    WrongNumberOfArguments {
+        function: Function,
+        found: usize,
+    },
     NoMoreToken,
-    RelationalOperatorNotFound,
     KeywordNotFound,
     LineNumberOutOfRange,
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnexpectedToken { expected, found } => {
+                write!(f, "expected {} but found {}", expected, found)
+            }
+            Error::TrailingToken { found } => {
+                write!(f, "expected : or the end of the line but found {}", found)
+            }
+            Error::VariableNotFound => write!(f, "expected a variable"),
+            Error::WrongNumberOfArguments { function, found } => {
+                let arity = function.arity();
+                let expected = match (arity.start(), arity.end()) {
+                    (1, 1) => "1 argument".to_string(),
+                    (start, end) if start == end => format!("{} arguments", start),
+                    (start, end) => format!("{} to {} arguments", start, end),
+                };
+                write!(f, "{} takes {} but got {}", function, expected, found)
+            }
+            Error::NoMoreToken => write!(f, "line ended unexpectedly"),
+            Error::KeywordNotFound => write!(f, "expected a statement"),
+            Error::LineNumberOutOfRange => {
+                write!(f, "line numbers must be between 1 and {}", MAX_LINE_NUMBER)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
 pub struct Parser {
-    tokens: VecDeque<Token>,
+    tokens: VecDeque<Spanned<Token>>,
+    /// Span of the token consumed last.
+    span: Span,
 }
 
 impl Parser {
-    pub fn new(tokens: VecDeque<Token>) -> Self {
-        Self { tokens }
+    pub fn new(tokens: VecDeque<Spanned<Token>>) -> Self {
+        Self {
+            tokens,
+            span: Span::default(),
+        }
     }
 
-    pub fn parse_line(&mut self) -> Result<Line, Error> {
+    /// Parses a line, or returns the error with the span of the token it was found at.
+    pub fn parse_line(&mut self) -> Result<Line, Spanned<Error>> {
+        self.parse_numbered_line().map_err(|error| {
+            let span = match error {
+                Error::NoMoreToken => Span::new(self.span.end(), self.span.end()),
+                _ => self.span,
+            };
+            Spanned::new(error, span)
+        })
+    }
+
+    fn parse_numbered_line(&mut self) -> Result<Line, Error> {
         let line_number = match self.peek_token() {
-            Some(Token::NumberLiteral(value)) => match u8::try_from(value) {
-                Ok(line_number) => {
-                    self.consume_token();
-                    Some(line_number)
-                }
-                Err(_) => Err(Error::LineNumberOutOfRange)?,
-            },
+            Some(Token::NumberLiteral(value)) => {
+                self.consume_token();
+                Some(Self::to_line_number(value)?)
+            }
+            // numbers too large for an integer are floats
+            Some(Token::FloatLiteral(_)) => Err(Error::LineNumberOutOfRange)?,
             _ => Option::None,
         };
 
-        let statement = self.parse_statement()?;
+        let statements = self.parse_statements()?;
+        if let Some(found) = self.consume_token() {
+            Err(Error::TrailingToken { found })?;
+        }
+
+        Ok(Line::new(line_number, statements))
+    }
 
-        Ok(Line::new(line_number, statement))
+    fn to_line_number(value: i16) -> Result<u16, Error> {
+        match u16::try_from(value) {
+            Ok(line_number @ 1..=MAX_LINE_NUMBER) => Ok(line_number),
+            _ => Err(Error::LineNumberOutOfRange),
+        }
     }
 
     fn consume_token(&mut self) -> Option<Token> {
-        self.tokens.pop_front()
+        let token = self.tokens.pop_front()?;
+        self.span = token.span();
+
+        Some(token.into_node())
     }
 
     fn peek_token(&self) -> Option<Token> {
-        self.tokens.front().cloned()
+        self.tokens.front().map(|token| token.node().clone())
     }
 
     fn expect(&mut self, expected: Token) -> Result<(), Error> {
@@ -66,25 +138,40 @@ impl Parser {
         }
     }
 
+    /// Parses statements separated by colons.
+    ///
+    /// An apostrophe comment may follow the last statement without a colon.
+    fn parse_statements(&mut self) -> Result<Vec<Spanned<Statement>>, Error> {
+        let mut statements = vec![self.parse_spanned_statement()?];
+
+        loop {
+            match self.peek_token() {
+                Some(Token::Colon) => {
+                    self.consume_token();
+                    statements.push(self.parse_spanned_statement()?);
+                }
+                Some(Token::Comment { .. }) => statements.push(self.parse_spanned_statement()?),
+                _ => break,
+            }
+        }
+
+        Ok(statements)
+    }
+
+    fn parse_spanned_statement(&mut self) -> Result<Spanned<Statement>, Error> {
+        let start = self.tokens.front().map_or(self.span, Spanned::span);
+        let statement = self.parse_statement()?;
+
+        Ok(Spanned::new(statement, start.to(self.span)))
+    }
+
     fn parse_statement(&mut self) -> Result<Statement, Error> {
         let statement = match self.consume_token() {
             Some(Token::Print) => {
                 let mut expression_list = Vec::new();
 
                 loop {
-                    match self.peek_token() {
-                        Some(Token::StringLiteral { value }) => {
-                            self.consume_token();
-                            let element =
-                                ExpressionListElement::StringLiteral(StringLiteral::new(value));
-                            expression_list.push(element);
-                        }
-                        _ => {
-                            let expression = self.parse_expression()?;
-                            let element = ExpressionListElement::Expression(expression);
-                            expression_list.push(element);
-                        }
-                    }
+                    expression_list.push(self.parse_expression()?);
 
                     if let Some(Token::Comma) = self.peek_token() {
                         self.consume_token();
@@ -96,24 +183,20 @@ impl Parser {
                 Statement::Print { expression_list }
             }
             Some(Token::If) => {
-                let left = self.parse_expression()?;
-                let operator = match self.consume_token() {
-                    Some(Token::Equal) => RelationalOperator::Equal,
-                    Some(Token::NotEqual) => RelationalOperator::NotEqual,
-                    Some(Token::LessThan) => RelationalOperator::LessThan,
-                    Some(Token::LessThanOrEqual) => RelationalOperator::LessThanOrEqual,
-                    Some(Token::GreaterThan) => RelationalOperator::GreaterThan,
-                    Some(Token::GreaterThanOrEqual) => RelationalOperator::GreaterThanOrEqual,
-                    _ => Err(Error::RelationalOperatorNotFound)?,
-                };
-                let right = self.parse_expression()?;
+                let condition = self.parse_expression()?;
                 self.expect(Token::Then)?;
-                let then = Box::new(self.parse_statement()?);
+                let then = self.parse_branch()?;
+                let otherwise = match self.peek_token() {
+                    Some(Token::Else) => {
+                        self.consume_token();
+                        Some(self.parse_branch()?)
+                    }
+                    _ => None,
+                };
                 Statement::If {
-                    left,
-                    operator,
-                    right,
+                    condition,
                     then,
+                    otherwise,
                 }
             }
             Some(Token::Goto) => {
@@ -123,13 +206,7 @@ impl Parser {
             Some(Token::Input) => {
                 let mut variable_list = Vec::new();
                 loop {
-                    match self.consume_token() {
-                        Some(Token::Variable { identifier }) => {
-                            let variable = Variable::new(identifier);
-                            variable_list.push(variable);
-                        }
-                        _ => Err(Error::VariableNotFound)?,
-                    }
+                    variable_list.push(self.parse_variable()?);
 
                     if let Some(Token::Comma) = self.peek_token() {
                         self.consume_token();
@@ -140,16 +217,32 @@ impl Parser {
                 Statement::Input { variable_list }
             }
             Some(Token::Let) => {
-                let variable = match self.consume_token() {
-                    Some(Token::Variable { identifier }) => Variable::new(identifier),
-                    _ => Err(Error::VariableNotFound)?,
+                let variable = self.parse_variable()?;
+                let target = match self.peek_token() {
+                    Some(Token::OpeningParenthesis) => {
+                        Target::ArrayElement(self.parse_array_element(variable)?)
+                    }
+                    _ => Target::Variable(variable),
                 };
                 self.expect(Token::Equal)?;
                 let expression = self.parse_expression()?;
-                Statement::Let {
-                    variable,
-                    expression,
+                Statement::Let { target, expression }
+            }
+            Some(Token::Dim) => {
+                let mut arrays = Vec::new();
+
+                loop {
+                    let variable = self.parse_variable()?;
+                    arrays.push(self.parse_array_element(variable)?);
+
+                    if let Some(Token::Comma) = self.peek_token() {
+                        self.consume_token();
+                    } else {
+                        break;
+                    }
                 }
+
+                Statement::Dim { arrays }
             }
             Some(Token::GoSub) => {
                 let expression = self.parse_expression()?;
@@ -160,6 +253,55 @@ impl Parser {
             Some(Token::List) => Statement::List,
             Some(Token::Run) => Statement::Run,
             Some(Token::End) => Statement::End,
+            Some(Token::Bye) => Statement::Bye,
+            Some(Token::For) => {
+                let variable = match self.consume_token() {
+                    Some(Token::Variable { name }) => Variable::new(name),
+                    _ => Err(Error::VariableNotFound)?,
+                };
+                self.expect(Token::Equal)?;
+                let from = self.parse_expression()?;
+                self.expect(Token::To)?;
+                let to = self.parse_expression()?;
+                let step = match self.peek_token() {
+                    Some(Token::Step) => {
+                        self.consume_token();
+                        Some(self.parse_expression()?)
+                    }
+                    _ => None,
+                };
+                Statement::For {
+                    variable,
+                    from,
+                    to,
+                    step,
+                }
+            }
+            Some(Token::Next) => {
+                let variable = match self.peek_token() {
+                    Some(Token::Variable { name }) => {
+                        self.consume_token();
+                        Some(Variable::new(name))
+                    }
+                    _ => None,
+                };
+                Statement::Next { variable }
+            }
+            Some(Token::While) => {
+                let condition = self.parse_expression()?;
+                Statement::While { condition }
+            }
+            Some(Token::Wend) => Statement::Wend,
+            Some(Token::Remark { text }) => Statement::Remark { text },
+            Some(Token::Comment { text }) => Statement::Comment { text },
+            Some(Token::Do) => {
+                let condition = self.parse_loop_condition()?;
+                Statement::Do { condition }
+            }
+            Some(Token::Loop) => {
+                let condition = self.parse_loop_condition()?;
+                Statement::Loop { condition }
+            }
             None => Err(Error::NoMoreToken)?,
             _ => Err(Error::KeywordNotFound)?,
         };
@@ -167,7 +309,116 @@ impl Parser {
         Ok(statement)
     }
 
+    fn parse_variable(&mut self) -> Result<Variable, Error> {
+        match self.consume_token() {
+            Some(Token::Variable { name }) => Ok(Variable::new(name)),
+            Some(Token::StringVariable { name }) => Ok(Variable::string(name)),
+            _ => Err(Error::VariableNotFound),
+        }
+    }
+
+    /// Parses the parenthesized indices following the name of an array.
+    fn parse_array_element(&mut self, variable: Variable) -> Result<ArrayElement, Error> {
+        let indices = self.parse_arguments()?;
+        Ok(ArrayElement::new(variable, indices))
+    }
+
+    /// Parses a parenthesized list of expressions like `(1, A + 2)`.
+    fn parse_arguments(&mut self) -> Result<Vec<Expression>, Error> {
+        self.expect(Token::OpeningParenthesis)?;
+        let mut arguments = vec![self.parse_expression()?];
+        while let Some(Token::Comma) = self.peek_token() {
+            self.consume_token();
+            arguments.push(self.parse_expression()?);
+        }
+        self.expect(Token::ClosingParenthesis)?;
+
+        Ok(arguments)
+    }
+
+    fn parse_branch(&mut self) -> Result<Branch, Error> {
+        match self.peek_token() {
+            Some(Token::NumberLiteral(value)) => {
+                self.consume_token();
+                Ok(Branch::LineNumber(Self::to_line_number(value)?))
+            }
+            _ => Ok(Branch::Statements(self.parse_statements()?)),
+        }
+    }
+
     fn parse_expression(&mut self) -> Result<Expression, Error> {
+        let mut expression = self.parse_conjunction()?;
+
+        while let Some(Token::Or) = self.peek_token() {
+            self.consume_token();
+            let right = self.parse_conjunction()?;
+            expression = Expression::Or(Box::new(expression), Box::new(right));
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_conjunction(&mut self) -> Result<Expression, Error> {
+        let mut expression = self.parse_negation()?;
+
+        while let Some(Token::And) = self.peek_token() {
+            self.consume_token();
+            let right = self.parse_negation()?;
+            expression = Expression::And(Box::new(expression), Box::new(right));
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_negation(&mut self) -> Result<Expression, Error> {
+        match self.peek_token() {
+            Some(Token::Not) => {
+                self.consume_token();
+                let expression = self.parse_negation()?;
+                Ok(Expression::Not(Box::new(expression)))
+            }
+            _ => self.parse_relation(),
+        }
+    }
+
+    fn parse_relation(&mut self) -> Result<Expression, Error> {
+        let left = self.parse_arithmetic_expression()?;
+        let operator = match self.peek_token() {
+            Some(Token::Equal) => RelationalOperator::Equal,
+            Some(Token::NotEqual) => RelationalOperator::NotEqual,
+            Some(Token::LessThan) => RelationalOperator::LessThan,
+            Some(Token::LessThanOrEqual) => RelationalOperator::LessThanOrEqual,
+            Some(Token::GreaterThan) => RelationalOperator::GreaterThan,
+            Some(Token::GreaterThanOrEqual) => RelationalOperator::GreaterThanOrEqual,
+            _ => return Ok(Expression::Arithmetic(left)),
+        };
+        self.consume_token();
+        let right = self.parse_arithmetic_expression()?;
+
+        Ok(Expression::Comparison {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        })
+    }
+
+    fn parse_loop_condition(&mut self) -> Result<Option<LoopCondition>, Error> {
+        let condition = match self.peek_token() {
+            Some(Token::While) => {
+                self.consume_token();
+                Some(LoopCondition::While(self.parse_expression()?))
+            }
+            Some(Token::Until) => {
+                self.consume_token();
+                Some(LoopCondition::Until(self.parse_expression()?))
+            }
+            _ => None,
+        };
+
+        Ok(condition)
+    }
+
+    fn parse_arithmetic_expression(&mut self) -> Result<ArithmeticExpression, Error> {
         let unary_operator = match self.peek_token() {
             Some(Token::Plus) => {
                 self.consume_token();
@@ -183,58 +434,126 @@ impl Parser {
         let mut others = Vec::new();
 
         loop {
-            match self.peek_token() {
-                Some(Token::Plus) => {
-                    self.consume_token();
-                    let term = self.parse_term()?;
-                    others.push((AdditiveOperator::Addition, term));
-                }
-                Some(Token::Minus) => {
-                    self.consume_token();
-                    let term = self.parse_term()?;
-                    others.push((AdditiveOperator::Subtraction, term));
-                }
+            let operator = match self.peek_token() {
+                Some(Token::Plus) => AdditiveOperator::Addition,
+                Some(Token::Minus) => AdditiveOperator::Subtraction,
                 _ => break,
-            }
+            };
+            self.consume_token();
+            let operator = Spanned::new(operator, self.span);
+            others.push((operator, self.parse_term()?));
         }
 
-        Ok(Expression::new(unary_operator, term, others))
+        Ok(ArithmeticExpression::new(unary_operator, term, others))
     }
 
     fn parse_term(&mut self) -> Result<Term, Error> {
-        let factor = self.parse_factor()?;
-        let mut operations = Vec::new();
+        self.parse_operations(MultiplicativeOperator::Modulo.precedence())
+    }
+
+    /// Parses factors combined by the multiplicative operators binding at least as tightly as
+    /// `precedence`, applying operators of the same precedence from left to right.
+    fn parse_operations(&mut self, precedence: u8) -> Result<Term, Error> {
+        let mut term = Term::Factor(self.parse_spanned_factor()?);
 
         loop {
-            match self.peek_token() {
-                Some(Token::Multiply) => {
-                    self.consume_token();
-                    let factor = self.parse_factor()?;
-                    operations.push((MultiplicativeOperator::Multiplication, factor));
-                }
-                Some(Token::Divide) => {
-                    self.consume_token();
-                    let factor = self.parse_factor()?;
-                    operations.push((MultiplicativeOperator::Division, factor));
-                }
+            let operator = match self.peek_token() {
+                Some(Token::Multiply) => MultiplicativeOperator::Multiplication,
+                Some(Token::Divide) => MultiplicativeOperator::Division,
+                Some(Token::IntegerDivide) => MultiplicativeOperator::IntegerDivision,
+                Some(Token::Mod) => MultiplicativeOperator::Modulo,
                 _ => break,
+            };
+            if operator.precedence() < precedence {
+                break;
             }
+
+            self.consume_token();
+            let operator = Spanned::new(operator, self.span);
+            let right = self.parse_operations(operator.node().precedence() + 1)?;
+            term = Term::Operation {
+                left: Box::new(term),
+                operator,
+                right: Box::new(right),
+            };
         }
 
-        Ok(Term::new(factor, operations))
+        Ok(term)
+    }
+
+    fn parse_spanned_factor(&mut self) -> Result<Spanned<Factor>, Error> {
+        let start = self.tokens.front().map_or(self.span, Spanned::span);
+        let factor = self.parse_factor()?;
+
+        Ok(Spanned::new(factor, start.to(self.span)))
     }
 
     fn parse_factor(&mut self) -> Result<Factor, Error> {
+        let base = self.parse_primary()?;
+
         match self.peek_token() {
-            Some(Token::Variable { identifier }) => {
+            Some(Token::Power) => {
                 self.consume_token();
-                let variable = Variable::new(identifier);
-                Ok(Factor::Variable(variable))
+                let exponent = self.parse_exponent()?;
+                Ok(Factor::Power {
+                    base: Box::new(base),
+                    exponent: Box::new(exponent),
+                })
+            }
+            _ => Ok(base),
+        }
+    }
+
+    /// Parses the exponent of a power, which may have a sign like in `2 ^ -1`.
+    fn parse_exponent(&mut self) -> Result<Factor, Error> {
+        let operator = match self.peek_token() {
+            Some(Token::Plus) => AdditiveOperator::Addition,
+            Some(Token::Minus) => AdditiveOperator::Subtraction,
+            _ => return self.parse_factor(),
+        };
+        self.consume_token();
+
+        Ok(Factor::Unary {
+            operator,
+            factor: Box::new(self.parse_factor()?),
+        })
+    }
+
+    fn parse_primary(&mut self) -> Result<Factor, Error> {
+        match self.peek_token() {
+            Some(Token::Variable { .. } | Token::StringVariable { .. }) => {
+                let variable = self.parse_variable()?;
+                match self.peek_token() {
+                    Some(Token::OpeningParenthesis) => {
+                        Ok(Factor::ArrayElement(self.parse_array_element(variable)?))
+                    }
+                    _ => Ok(Factor::Variable(variable)),
+                }
+            }
+            Some(Token::Function(function)) => {
+                self.consume_token();
+                let arguments = self.parse_arguments()?;
+                if !function.arity().contains(&arguments.len()) {
+                    Err(Error::WrongNumberOfArguments {
+                        function,
+                        found: arguments.len(),
+                    })?;
+                }
+
+                Ok(Factor::FunctionCall(FunctionCall::new(function, arguments)))
             }
             Some(Token::NumberLiteral(value)) => {
                 self.consume_token();
                 Ok(Factor::NumberLiteral(NumberLiteral::new(value)))
             }
+            Some(Token::FloatLiteral(value)) => {
+                self.consume_token();
+                Ok(Factor::FloatLiteral(FloatLiteral::new(value)))
+            }
+            Some(Token::StringLiteral { value }) => {
+                self.consume_token();
+                Ok(Factor::StringLiteral(StringLiteral::new(value)))
+            }
             _ => {
                 self.expect(Token::OpeningParenthesis)?;
                 let expression = Box::new(self.parse_expression()?);
@@ -248,6 +567,11 @@ impl Parser {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lexer::lex;
+
+    fn spanned(tokens: VecDeque<Token>) -> VecDeque<Spanned<Token>> {
+        tokens.into_iter().map(Spanned::from).collect()
+    }
 
     #[test]
     fn parse_line_hello_world_returns_ast() {
@@ -260,13 +584,19 @@ mod tests {
         ]);
         let expected = Line::new(
             Some(10),
-            Statement::Print {
-                expression_list: vec![ExpressionListElement::StringLiteral(StringLiteral::new(
-                    b"Hello, World!".to_vec(),
+            vec![Statement::Print {
+                expression_list: vec![Expression::Arithmetic(ArithmeticExpression::new(
+                    None,
+                    Term::new(
+                        Factor::StringLiteral(StringLiteral::new(b"Hello, World!".to_vec())),
+                        vec![],
+                    ),
+                    vec![],
                 ))],
-            },
+            }
+            .into()],
         );
-        let mut parser = Parser::new(tokens);
+        let mut parser = Parser::new(spanned(tokens));
 
         let actual = parser.parse_line();
 
@@ -285,18 +615,19 @@ mod tests {
 
         let expected = Line::new(
             Some(10),
-            Statement::Print {
-                expression_list: vec![ExpressionListElement::Expression(Expression::new(
+            vec![Statement::Print {
+                expression_list: vec![Expression::Arithmetic(ArithmeticExpression::new(
                     None,
                     Term::new(Factor::NumberLiteral(NumberLiteral::new(2)), vec![]),
                     vec![(
-                        AdditiveOperator::Addition,
+                        AdditiveOperator::Addition.into(),
                         Term::new(Factor::NumberLiteral(NumberLiteral::new(3)), vec![]),
                     )],
                 ))],
-            },
+            }
+            .into()],
         );
-        let mut parser = Parser::new(tokens);
+        let mut parser = Parser::new(spanned(tokens));
 
         let actual = parser.parse_line();
 
@@ -315,21 +646,766 @@ mod tests {
         ]);
         let expected = Line::new(
             Some(10),
-            Statement::Print {
-                expression_list: vec![ExpressionListElement::Expression(Expression::new(
+            vec![Statement::Print {
+                expression_list: vec![Expression::Arithmetic(ArithmeticExpression::new(
                     Some(AdditiveOperator::Subtraction),
                     Term::new(Factor::NumberLiteral(NumberLiteral::new(2)), vec![]),
                     vec![(
-                        AdditiveOperator::Addition,
+                        AdditiveOperator::Addition.into(),
                         Term::new(Factor::NumberLiteral(NumberLiteral::new(3)), vec![]),
                     )],
                 ))],
+            }
+            .into()],
+        );
+        let mut parser = Parser::new(spanned(tokens));
+
+        let actual = parser.parse_line();
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn parse_line_power_is_right_associative() {
+        let tokens = VecDeque::from([
+            Token::Print,
+            Token::NumberLiteral(2),
+            Token::Power,
+            Token::NumberLiteral(3),
+            Token::Power,
+            Token::NumberLiteral(2),
+            Token::Mod,
+            Token::NumberLiteral(5),
+        ]);
+        let number = |value| Box::new(Factor::NumberLiteral(NumberLiteral::new(value)));
+        let expected = Line::new(
+            None,
+            vec![Statement::Print {
+                expression_list: vec![Expression::Arithmetic(ArithmeticExpression::new(
+                    None,
+                    Term::new(
+                        Factor::Power {
+                            base: number(2),
+                            exponent: Box::new(Factor::Power {
+                                base: number(3),
+                                exponent: number(2),
+                            }),
+                        },
+                        vec![(MultiplicativeOperator::Modulo, *number(5))],
+                    ),
+                    vec![],
+                ))],
+            }
+            .into()],
+        );
+        let mut parser = Parser::new(spanned(tokens));
+
+        let actual = parser.parse_line();
+
+        assert_eq!(Ok(expected), actual);
+        assert_eq!("PRINT 2 ^ 3 ^ 2 MOD 5", actual.unwrap().to_string());
+    }
+
+    #[test]
+    fn parse_line_integer_division_and_mod_bind_less_tightly_returns_ast() {
+        let tokens = lex(b"PRINT A MOD B \\ C * D MOD E").unwrap();
+        let variable = |name| Box::new(Term::Factor(Factor::Variable(Variable::new(name)).into()));
+        let operation = |left, operator: MultiplicativeOperator, right| {
+            Box::new(Term::Operation {
+                left,
+                operator: operator.into(),
+                right,
+            })
+        };
+        let term = operation(
+            operation(
+                variable("A"),
+                MultiplicativeOperator::Modulo,
+                operation(
+                    variable("B"),
+                    MultiplicativeOperator::IntegerDivision,
+                    operation(
+                        variable("C"),
+                        MultiplicativeOperator::Multiplication,
+                        variable("D"),
+                    ),
+                ),
+            ),
+            MultiplicativeOperator::Modulo,
+            variable("E"),
+        );
+        let expected = Statement::Print {
+            expression_list: vec![Expression::Arithmetic(ArithmeticExpression::new(
+                None,
+                *term,
+                vec![],
+            ))],
+        };
+        let mut parser = Parser::new(tokens.into());
+
+        let actual = parser.parse_line();
+
+        assert_eq!(Ok(Line::new(None, vec![expected.into()])), actual);
+        assert_eq!("PRINT A MOD B \\ C * D MOD E", actual.unwrap().to_string());
+    }
+
+    #[test]
+    fn parse_line_power_with_signed_exponent_returns_ast() {
+        let tokens = lex(b"PRINT 2 ^ -X").unwrap();
+        let expected = Statement::Print {
+            expression_list: vec![Expression::Arithmetic(ArithmeticExpression::new(
+                None,
+                Term::new(
+                    Factor::Power {
+                        base: Box::new(Factor::NumberLiteral(NumberLiteral::new(2))),
+                        exponent: Box::new(Factor::Unary {
+                            operator: AdditiveOperator::Subtraction,
+                            factor: Box::new(Factor::Variable(Variable::new("X"))),
+                        }),
+                    },
+                    vec![],
+                ),
+                vec![],
+            ))],
+        };
+        let mut parser = Parser::new(tokens.into());
+
+        let actual = parser.parse_line();
+
+        assert_eq!(Ok(Line::new(None, vec![expected.into()])), actual);
+        assert_eq!("PRINT 2 ^ -X", actual.unwrap().to_string());
+    }
+
+    #[test]
+    fn parse_line_for_with_step_returns_ast() {
+        let tokens = VecDeque::from([
+            Token::For,
+            Token::Variable { name: "I".into() },
+            Token::Equal,
+            Token::NumberLiteral(10),
+            Token::To,
+            Token::NumberLiteral(1),
+            Token::Step,
+            Token::Minus,
+            Token::NumberLiteral(2),
+        ]);
+        let number = |value| {
+            Expression::Arithmetic(ArithmeticExpression::new(
+                None,
+                Term::new(Factor::NumberLiteral(NumberLiteral::new(value)), vec![]),
+                vec![],
+            ))
+        };
+        let expected = Line::new(
+            None,
+            vec![Statement::For {
+                variable: Variable::new("I"),
+                from: number(10),
+                to: number(1),
+                step: Some(Expression::Arithmetic(ArithmeticExpression::new(
+                    Some(AdditiveOperator::Subtraction),
+                    Term::new(Factor::NumberLiteral(NumberLiteral::new(2)), vec![]),
+                    vec![],
+                ))),
+            }
+            .into()],
+        );
+        let mut parser = Parser::new(spanned(tokens));
+
+        let actual = parser.parse_line();
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn parse_line_next_without_variable_returns_ast() {
+        let tokens = VecDeque::from([Token::NumberLiteral(20), Token::Next]);
+        let expected = Line::new(Some(20), vec![Statement::Next { variable: None }.into()]);
+        let mut parser = Parser::new(spanned(tokens));
+
+        let actual = parser.parse_line();
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn parse_line_loop_until_returns_ast() {
+        let tokens = VecDeque::from([
+            Token::Loop,
+            Token::Until,
+            Token::Variable { name: "A".into() },
+            Token::GreaterThan,
+            Token::NumberLiteral(3),
+        ]);
+        let expected = Line::new(
+            None,
+            vec![Statement::Loop {
+                condition: Some(LoopCondition::Until(Expression::Comparison {
+                    left: Box::new(ArithmeticExpression::new(
+                        None,
+                        Term::new(Factor::Variable(Variable::new("A")), vec![]),
+                        vec![],
+                    )),
+                    operator: RelationalOperator::GreaterThan,
+                    right: Box::new(ArithmeticExpression::new(
+                        None,
+                        Term::new(Factor::NumberLiteral(NumberLiteral::new(3)), vec![]),
+                        vec![],
+                    )),
+                })),
+            }
+            .into()],
+        );
+        let mut parser = Parser::new(spanned(tokens));
+
+        let actual = parser.parse_line();
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn parse_line_while_without_condition_returns_error() {
+        let tokens = VecDeque::from([Token::While]);
+        let mut parser = Parser::new(spanned(tokens));
+
+        let actual = parser.parse_line();
+
+        assert_eq!(Err(Error::NoMoreToken.into()), actual);
+    }
+
+    #[test]
+    fn parse_line_let_with_comparison_returns_ast() {
+        let tokens = VecDeque::from([
+            Token::Let,
+            Token::Variable { name: "F".into() },
+            Token::Equal,
+            Token::OpeningParenthesis,
+            Token::Variable { name: "A".into() },
+            Token::LessThan,
+            Token::Variable { name: "B".into() },
+            Token::ClosingParenthesis,
+            Token::Plus,
+            Token::Variable { name: "C".into() },
+            Token::Equal,
+            Token::NumberLiteral(1),
+        ]);
+        let mut parser = Parser::new(spanned(tokens));
+
+        let actual = parser.parse_line().unwrap();
+
+        assert_eq!("LET F = (A < B) + C = 1", actual.to_string());
+    }
+
+    #[test]
+    fn parse_line_dim_returns_ast() {
+        let tokens = lex(b"10 DIM A(10), M(2, N + 1)").unwrap();
+        let mut parser = Parser::new(tokens.into());
+
+        let actual = parser.parse_line().unwrap();
+
+        assert_eq!("10 DIM A(10), M(2, N + 1)", actual.to_string());
+    }
+
+    #[test]
+    fn parse_line_let_array_element_returns_ast() {
+        let tokens = lex(b"LET M(I, 1) = A(I) * 2").unwrap();
+        let expected = Line::new(
+            None,
+            vec![Statement::Let {
+                target: Target::ArrayElement(ArrayElement::new(
+                    Variable::new("M"),
+                    vec![
+                        Expression::Arithmetic(ArithmeticExpression::new(
+                            None,
+                            Term::new(Factor::Variable(Variable::new("I")), vec![]),
+                            vec![],
+                        )),
+                        Expression::Arithmetic(ArithmeticExpression::new(
+                            None,
+                            Term::new(Factor::NumberLiteral(NumberLiteral::new(1)), vec![]),
+                            vec![],
+                        )),
+                    ],
+                )),
+                expression: Expression::Arithmetic(ArithmeticExpression::new(
+                    None,
+                    Term::new(
+                        Factor::ArrayElement(ArrayElement::new(
+                            Variable::new("A"),
+                            vec![Expression::Arithmetic(ArithmeticExpression::new(
+                                None,
+                                Term::new(Factor::Variable(Variable::new("I")), vec![]),
+                                vec![],
+                            ))],
+                        )),
+                        vec![(
+                            MultiplicativeOperator::Multiplication,
+                            Factor::NumberLiteral(NumberLiteral::new(2)),
+                        )],
+                    ),
+                    vec![],
+                )),
+            }
+            .into()],
+        );
+        let mut parser = Parser::new(tokens.into());
+
+        let actual = parser.parse_line();
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn parse_line_string_expressions_returns_ast() {
+        let tokens = lex(b"10 IF A$ + \"!\" = B$(1) THEN PRINT \"HI\", A$").unwrap();
+        let mut parser = Parser::new(tokens.into());
+
+        let actual = parser.parse_line().unwrap();
+
+        assert_eq!(
+            "10 IF A$ + \"!\" = B$(1) THEN PRINT \"HI\", A$",
+            actual.to_string()
+        );
+    }
+
+    #[test]
+    fn parse_line_float_literals_returns_ast() {
+        let tokens = lex(b"LET A = 1.5E-3 + .5 * 2E20").unwrap();
+        let mut parser = Parser::new(tokens.into());
+
+        let actual = parser.parse_line().unwrap();
+
+        assert_eq!("LET A = 0.0015 + 0.5 * 2E20", actual.to_string());
+    }
+
+    #[test]
+    fn parse_line_integer_too_large_returns_float_literal() {
+        let tokens = lex(b"10 LET A = 40000 + 2.0").unwrap();
+        let mut parser = Parser::new(tokens.into());
+
+        let actual = parser.parse_line().unwrap();
+
+        let Statement::Let { expression, .. } = actual.statements()[0].node() else {
+            panic!("expected LET, got {actual}");
+        };
+        assert_eq!(
+            &Expression::Arithmetic(ArithmeticExpression::new(
+                None,
+                Term::new(Factor::FloatLiteral(FloatLiteral::new(40000.0)), vec![]),
+                vec![(
+                    AdditiveOperator::Addition.into(),
+                    Term::new(Factor::FloatLiteral(FloatLiteral::new(2.0)), vec![])
+                )],
+            )),
+            expression
+        );
+        assert_eq!("10 LET A = 40000 + 2.0", actual.to_string());
+    }
+
+    #[test]
+    fn parse_line_function_call_returns_ast() {
+        let tokens = lex(b"PRINT SQR(ABS(X) + 1) * 2").unwrap();
+        let expected = Statement::Print {
+            expression_list: vec![Expression::Arithmetic(ArithmeticExpression::new(
+                None,
+                Term::new(
+                    Factor::FunctionCall(FunctionCall::new(
+                        Function::Sqr,
+                        vec![Expression::Arithmetic(ArithmeticExpression::new(
+                            None,
+                            Term::new(
+                                Factor::FunctionCall(FunctionCall::new(
+                                    Function::Abs,
+                                    vec![Expression::Arithmetic(ArithmeticExpression::new(
+                                        None,
+                                        Term::new(Factor::Variable(Variable::new("X")), vec![]),
+                                        vec![],
+                                    ))],
+                                )),
+                                vec![],
+                            ),
+                            vec![(
+                                AdditiveOperator::Addition.into(),
+                                Term::new(Factor::NumberLiteral(NumberLiteral::new(1)), vec![]),
+                            )],
+                        ))],
+                    )),
+                    vec![(
+                        MultiplicativeOperator::Multiplication,
+                        Factor::NumberLiteral(NumberLiteral::new(2)),
+                    )],
+                ),
+                vec![],
+            ))],
+        };
+        let mut parser = Parser::new(tokens.into());
+
+        let actual = parser.parse_line();
+
+        assert_eq!(Ok(Line::new(None, vec![expected.into()])), actual);
+        assert_eq!("PRINT SQR(ABS(X) + 1) * 2", actual.unwrap().to_string());
+    }
+
+    #[test]
+    fn parse_line_function_with_wrong_number_of_arguments_returns_error() {
+        let tokens = lex(b"PRINT SIN(1, 2)").unwrap();
+        let mut parser = Parser::new(tokens.into());
+
+        let actual = parser.parse_line();
+
+        assert_eq!(
+            Err(Error::WrongNumberOfArguments {
+                function: Function::Sin,
+                found: 2,
+            }
+            .into()),
+            actual
+        );
+        assert_eq!(
+            "SIN takes 1 argument but got 2",
+            actual.unwrap_err().node().to_string()
+        );
+    }
+
+    #[test]
+    fn parse_line_dim_without_bounds_returns_error() {
+        let tokens = lex(b"DIM A").unwrap();
+        let mut parser = Parser::new(tokens.into());
+
+        let actual = parser.parse_line();
+
+        assert_eq!(Err(Error::NoMoreToken.into()), actual);
+    }
+
+    #[test]
+    fn parse_line_with_colons_returns_statements() {
+        let tokens = VecDeque::from([
+            Token::NumberLiteral(10),
+            Token::If,
+            Token::Variable { name: "A".into() },
+            Token::Equal,
+            Token::NumberLiteral(1),
+            Token::Then,
+            Token::Return,
+            Token::Colon,
+            Token::End,
+        ]);
+        let expected = Line::new(
+            Some(10),
+            vec![Statement::If {
+                condition: Expression::Comparison {
+                    left: Box::new(ArithmeticExpression::new(
+                        None,
+                        Term::new(Factor::Variable(Variable::new("A")), vec![]),
+                        vec![],
+                    )),
+                    operator: RelationalOperator::Equal,
+                    right: Box::new(ArithmeticExpression::new(
+                        None,
+                        Term::new(Factor::NumberLiteral(NumberLiteral::new(1)), vec![]),
+                        vec![],
+                    )),
+                },
+                then: Branch::Statements(vec![Statement::Return.into(), Statement::End.into()]),
+                otherwise: None,
+            }
+            .into()],
+        );
+        let mut parser = Parser::new(spanned(tokens));
+
+        let actual = parser.parse_line();
+
+        assert_eq!(Ok(expected), actual);
+        assert_eq!("10 IF A = 1 THEN RETURN: END", actual.unwrap().to_string());
+    }
+
+    #[test]
+    fn parse_line_if_with_line_numbers_returns_ast() {
+        let tokens = VecDeque::from([
+            Token::If,
+            Token::NumberLiteral(1),
+            Token::LessThan,
+            Token::NumberLiteral(2),
+            Token::Then,
+            Token::NumberLiteral(100),
+            Token::Else,
+            Token::NumberLiteral(200),
+        ]);
+        let number = |value| {
+            ArithmeticExpression::new(
+                None,
+                Term::new(Factor::NumberLiteral(NumberLiteral::new(value)), vec![]),
+                vec![],
+            )
+        };
+        let expected = Line::new(
+            None,
+            vec![Statement::If {
+                condition: Expression::Comparison {
+                    left: Box::new(number(1)),
+                    operator: RelationalOperator::LessThan,
+                    right: Box::new(number(2)),
+                },
+                then: Branch::LineNumber(100),
+                otherwise: Some(Branch::LineNumber(200)),
+            }
+            .into()],
+        );
+        let mut parser = Parser::new(spanned(tokens));
+
+        let actual = parser.parse_line();
+
+        assert_eq!(Ok(expected), actual);
+        assert_eq!("IF 1 < 2 THEN 100 ELSE 200", actual.unwrap().to_string());
+    }
+
+    #[test]
+    fn parse_line_nested_if_binds_else_to_inner_if() {
+        let tokens = VecDeque::from([
+            Token::If,
+            Token::NumberLiteral(1),
+            Token::Equal,
+            Token::NumberLiteral(1),
+            Token::Then,
+            Token::If,
+            Token::NumberLiteral(2),
+            Token::Equal,
+            Token::NumberLiteral(2),
+            Token::Then,
+            Token::Return,
+            Token::Else,
+            Token::End,
+        ]);
+        let mut parser = Parser::new(spanned(tokens));
+
+        let actual = parser.parse_line().unwrap();
+
+        assert_eq!(
+            "IF 1 = 1 THEN IF 2 = 2 THEN RETURN ELSE END",
+            actual.to_string()
+        );
+        let Statement::If { otherwise, .. } = actual.statements()[0].node() else {
+            panic!("expected IF statement");
+        };
+        assert_eq!(&None, otherwise);
+    }
+
+    #[test]
+    fn parse_line_boolean_operators_have_precedence() {
+        let tokens = VecDeque::from([
+            Token::While,
+            Token::Not,
+            Token::Variable { name: "A".into() },
+            Token::Equal,
+            Token::NumberLiteral(1),
+            Token::Or,
+            Token::Variable { name: "B".into() },
+            Token::Equal,
+            Token::NumberLiteral(2),
+            Token::And,
+            Token::Variable { name: "C".into() },
+            Token::Equal,
+            Token::NumberLiteral(3),
+        ]);
+        let comparison = |name: &str, value| {
+            Box::new(Expression::Comparison {
+                left: Box::new(ArithmeticExpression::new(
+                    None,
+                    Term::new(Factor::Variable(Variable::new(name)), vec![]),
+                    vec![],
+                )),
+                operator: RelationalOperator::Equal,
+                right: Box::new(ArithmeticExpression::new(
+                    None,
+                    Term::new(Factor::NumberLiteral(NumberLiteral::new(value)), vec![]),
+                    vec![],
+                )),
+            })
+        };
+        let expected = Line::new(
+            None,
+            vec![Statement::While {
+                condition: Expression::Or(
+                    Box::new(Expression::Not(comparison("A", 1))),
+                    Box::new(Expression::And(comparison("B", 2), comparison("C", 3))),
+                ),
+            }
+            .into()],
+        );
+        let mut parser = Parser::new(spanned(tokens));
+
+        let actual = parser.parse_line();
+
+        assert_eq!(Ok(expected), actual);
+    }
+
+    #[test]
+    fn parse_line_parenthesized_condition_round_trips() {
+        let tokens = VecDeque::from([
+            Token::If,
+            Token::OpeningParenthesis,
+            Token::Variable { name: "A".into() },
+            Token::Plus,
+            Token::NumberLiteral(1),
+            Token::ClosingParenthesis,
+            Token::GreaterThan,
+            Token::NumberLiteral(0),
+            Token::And,
+            Token::Not,
+            Token::OpeningParenthesis,
+            Token::Variable { name: "B".into() },
+            Token::LessThan,
+            Token::NumberLiteral(1),
+            Token::Or,
+            Token::Variable { name: "B".into() },
+            Token::GreaterThan,
+            Token::NumberLiteral(9),
+            Token::ClosingParenthesis,
+            Token::Then,
+            Token::End,
+        ]);
+        let mut parser = Parser::new(spanned(tokens));
+
+        let actual = parser.parse_line().unwrap();
+
+        assert_eq!(
+            "IF (A + 1) > 0 AND NOT (B < 1 OR B > 9) THEN END",
+            actual.to_string()
+        );
+    }
+
+    #[test]
+    fn parse_line_with_trailing_colon_returns_error() {
+        let tokens = VecDeque::from([Token::Return, Token::Colon]);
+        let mut parser = Parser::new(spanned(tokens));
+
+        let actual = parser.parse_line();
+
+        assert_eq!(Err(Error::NoMoreToken.into()), actual);
+    }
+
+    #[test]
+    fn parse_line_with_trailing_expression_returns_error() {
+        let tokens = VecDeque::from([
+            Token::Print,
+            Token::NumberLiteral(1),
+            Token::NumberLiteral(2),
+            Token::NumberLiteral(3),
+        ]);
+        let mut parser = Parser::new(spanned(tokens));
+
+        let actual = parser.parse_line();
+
+        let expected = Error::TrailingToken {
+            found: Token::NumberLiteral(2),
+        };
+        assert_eq!(Err(expected.into()), actual);
+    }
+
+    #[test]
+    fn parse_line_with_trailing_variable_returns_error_with_span() {
+        let tokens = lex(b"LET A = 1 B").unwrap();
+        let mut parser = Parser::new(VecDeque::from(tokens));
+
+        let actual = parser.parse_line();
+
+        let error = actual.unwrap_err();
+        let expected = Error::TrailingToken {
+            found: Token::Variable { name: "B".into() },
+        };
+        assert_eq!(&expected, error.node());
+        assert_eq!(Span::new(10, 11), error.span());
+    }
+
+    #[test]
+    fn parse_line_with_stray_else_returns_error() {
+        let tokens = VecDeque::from([Token::Return, Token::Else, Token::End]);
+        let mut parser = Parser::new(spanned(tokens));
+
+        let actual = parser.parse_line();
+
+        let expected = Error::TrailingToken { found: Token::Else };
+        assert_eq!(Err(expected.into()), actual);
+    }
+
+    #[test]
+    fn parse_line_with_comment_after_statement_returns_ast() {
+        let tokens = VecDeque::from([
+            Token::NumberLiteral(10),
+            Token::Return,
+            Token::Comment {
+                text: b" done".to_vec(),
             },
+        ]);
+        let expected = Line::new(
+            Some(10),
+            vec![
+                Statement::Return.into(),
+                Statement::Comment {
+                    text: b" done".to_vec(),
+                }
+                .into(),
+            ],
         );
-        let mut parser = Parser::new(tokens);
+        let mut parser = Parser::new(spanned(tokens));
+
+        let actual = parser.parse_line();
+
+        assert_eq!(Ok(expected), actual);
+        assert_eq!("10 RETURN ' done", actual.unwrap().to_string());
+    }
+
+    #[test]
+    fn parse_line_with_large_line_number_returns_ast() {
+        let tokens = VecDeque::from([Token::NumberLiteral(32000), Token::Return]);
+        let expected = Line::new(Some(32000), vec![Statement::Return.into()]);
+        let mut parser = Parser::new(spanned(tokens));
 
         let actual = parser.parse_line();
 
         assert_eq!(Ok(expected), actual);
     }
+
+    #[test]
+    fn parse_line_with_zero_line_number_returns_error() {
+        let tokens = VecDeque::from([Token::NumberLiteral(0), Token::Return]);
+        let mut parser = Parser::new(spanned(tokens));
+
+        let actual = parser.parse_line();
+
+        assert_eq!(Err(Error::LineNumberOutOfRange.into()), actual);
+    }
+
+    #[test]
+    fn parse_line_with_too_large_line_number_returns_error() {
+        let tokens = lex(b"40000 RETURN").unwrap();
+        let mut parser = Parser::new(tokens.into());
+
+        let actual = parser.parse_line();
+
+        assert_eq!(Err(Error::LineNumberOutOfRange.into()), actual);
+    }
+
+    #[test]
+    fn parse_line_returns_spans_of_statements() {
+        let tokens = lex(b"10 LET A = 1: RETURN").unwrap();
+        let mut parser = Parser::new(VecDeque::from(tokens));
+
+        let actual = parser.parse_line().unwrap();
+
+        let spans: Vec<Span> = actual.statements().iter().map(Spanned::span).collect();
+        assert_eq!(vec![Span::new(3, 12), Span::new(14, 20)], spans);
+    }
+
+    #[test]
+    fn parse_line_returns_error_with_span_of_token() {
+        let tokens = lex(b"LET 5 = 2").unwrap();
+        let mut parser = Parser::new(VecDeque::from(tokens));
+
+        let actual = parser.parse_line();
+
+        let error = actual.unwrap_err();
+        assert_eq!(&Error::VariableNotFound, error.node());
+        assert_eq!(Span::new(4, 5), error.span());
+    }
 }
@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+
+use crate::ast::{
+    ArithmeticExpression, Branch, Expression, Factor, Line, NumberLiteral, Statement, Term,
+};
+use crate::span::{Span, Spanned};
+
+/// Location of an instruction, either in a stored line or, if `line` is `None`, in the line
+/// being run directly.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Position {
+    line: Option<u16>,
+    index: usize,
+}
+
+impl Position {
+    pub const fn new(line: Option<u16>, index: usize) -> Self {
+        Self { line, index }
+    }
+
+    pub fn line(&self) -> Option<u16> {
+        self.line
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the position of the instruction following this one on the same line.
+    pub fn next(&self) -> Self {
+        Self::new(self.line, self.index + 1)
+    }
+}
+
+/// A statement of a line flattened for execution.
+///
+/// The statements guarded by `IF` are inlined after it, so that every statement of a line has
+/// an index a [`Position`] can point to.
+// most instructions are statements, so boxing them would not save memory
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, PartialEq, Clone)]
+pub enum Instruction {
+    Statement(Statement),
+    /// Continues with the next instruction if `condition` holds, or jumps to `otherwise`.
+    If {
+        condition: Expression,
+        otherwise: usize,
+    },
+    /// Jumps to the instruction at `index` of the same line.
+    Jump(usize),
+}
+
+/// Compiles `statements`, giving every instruction the span of the statement it came from.
+fn compile(statements: &[Spanned<Statement>], instructions: &mut Vec<Spanned<Instruction>>) {
+    for statement in statements {
+        let span = statement.span();
+        match statement.node() {
+            Statement::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let branch = instructions.len();
+                let instruction = Instruction::If {
+                    condition: condition.clone(),
+                    otherwise: 0,
+                };
+                instructions.push(Spanned::new(instruction, span));
+                compile_branch(then, span, instructions);
+
+                if let Some(otherwise) = otherwise {
+                    let jump = instructions.len();
+                    instructions.push(Spanned::new(Instruction::Jump(0), span));
+                    patch(instructions, branch);
+                    compile_branch(otherwise, span, instructions);
+                    patch(instructions, jump);
+                } else {
+                    patch(instructions, branch);
+                }
+            }
+            statement => {
+                let instruction = Instruction::Statement(statement.clone());
+                instructions.push(Spanned::new(instruction, span));
+            }
+        }
+    }
+}
+
+/// Compiles a branch of the `IF` statement at `span`.
+fn compile_branch(branch: &Branch, span: Span, instructions: &mut Vec<Spanned<Instruction>>) {
+    match branch {
+        Branch::LineNumber(line_number) => {
+            let line_number = NumberLiteral::new(*line_number as i16);
+            let expression = Expression::Arithmetic(ArithmeticExpression::new(
+                None,
+                Term::new(Factor::NumberLiteral(line_number), vec![]),
+                vec![],
+            ));
+            let instruction = Instruction::Statement(Statement::Goto { expression });
+            instructions.push(Spanned::new(instruction, span));
+        }
+        Branch::Statements(statements) => compile(statements, instructions),
+    }
+}
+
+/// Makes the jump at `index` continue after the last instruction compiled so far.
+fn patch(instructions: &mut [Spanned<Instruction>], index: usize) {
+    let end = instructions.len();
+    match instructions[index].node_mut() {
+        Instruction::If { otherwise, .. } => *otherwise = end,
+        Instruction::Jump(target) => *target = end,
+        Instruction::Statement(_) => unreachable!("only jumps can be patched"),
+    }
+}
+
+#[derive(Debug)]
+struct StoredLine {
+    line: Line,
+    instructions: Vec<Spanned<Instruction>>,
+}
+
+/// The stored program and the line being run directly.
+#[derive(Debug, Default)]
+pub struct Program {
+    lines: BTreeMap<u16, StoredLine>,
+    direct: Vec<Spanned<Instruction>>,
+}
+
+impl Program {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, number: u16, line: Line) {
+        let mut instructions = Vec::new();
+        compile(line.statements(), &mut instructions);
+
+        self.lines.insert(number, StoredLine { line, instructions });
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    pub fn contains(&self, number: u16) -> bool {
+        self.lines.contains_key(&number)
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = &Line> {
+        self.lines.values().map(|stored_line| &stored_line.line)
+    }
+
+    pub fn line(&self, number: u16) -> Option<&Line> {
+        self.lines.get(&number).map(|stored_line| &stored_line.line)
+    }
+
+    /// Replaces the line being run directly.
+    pub fn set_direct(&mut self, statements: &[Spanned<Statement>]) {
+        self.direct.clear();
+        compile(statements, &mut self.direct);
+    }
+
+    /// Returns the instruction at `position`, or the first one after it.
+    pub fn instruction_at(&self, position: Position) -> Option<(Position, &Spanned<Instruction>)> {
+        self.instructions_from(position).next()
+    }
+
+    /// Iterates over the instructions in program order, starting at `position`.
+    ///
+    /// A position in a stored line continues to the following stored lines, while the line run
+    /// directly ends with its last instruction.
+    pub fn instructions_from(
+        &self,
+        position: Position,
+    ) -> Box<dyn Iterator<Item = (Position, &Spanned<Instruction>)> + '_> {
+        match position.line() {
+            Some(start) => Box::new(self.lines.range(start..).flat_map(
+                move |(&number, stored_line)| {
+                    let skip = if number == start { position.index() } else { 0 };
+                    stored_line.instructions.iter().enumerate().skip(skip).map(
+                        move |(index, instruction)| {
+                            (Position::new(Some(number), index), instruction)
+                        },
+                    )
+                },
+            )),
+            None => Box::new(
+                self.direct
+                    .iter()
+                    .enumerate()
+                    .skip(position.index())
+                    .map(|(index, instruction)| (Position::new(None, index), instruction)),
+            ),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+/// Byte range `start..end` of a piece of source text.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Span {
+    start: usize,
+    end: usize,
+}
+
+impl Span {
+    pub const fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Returns the span covering both this span and `other`.
+    pub fn to(&self, other: Span) -> Self {
+        Self::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    /// Renders the line of `source` containing the span with carets under it.
+    pub fn underline(&self, source: &str) -> String {
+        let source = source.trim_end_matches(['\r', '\n']);
+        let start = self.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |index| start + index);
+        let width = self.end.min(line_end).saturating_sub(start).max(1);
+
+        format!(
+            "{}\n{}{}",
+            &source[line_start..line_end],
+            " ".repeat(source[line_start..start].chars().count()),
+            "^".repeat(width)
+        )
+    }
+}
+
+/// A value with the span of the source text it was read from.
+///
+/// Spans are not compared, so that values read from differently formatted source are equal.
+#[derive(Debug, Clone)]
+pub struct Spanned<T> {
+    node: T,
+    span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Self {
+        Self { node, span }
+    }
+
+    pub fn node(&self) -> &T {
+        &self.node
+    }
+
+    pub fn node_mut(&mut self) -> &mut T {
+        &mut self.node
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn into_node(self) -> T {
+        self.node
+    }
+}
+
+/// Wraps a value with an empty span, e.g. for values that were not read from source text.
+impl<T> From<T> for Spanned<T> {
+    fn from(node: T) -> Self {
+        Self::new(node, Span::default())
+    }
+}
+
+impl<T: PartialEq> PartialEq for Spanned<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.node == other.node
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn underline_marks_span_with_carets() {
+        let span = Span::new(9, 12);
+
+        let actual = span.underline("10 PRINT 1/0\n");
+
+        assert_eq!("10 PRINT 1/0\n         ^^^", actual);
+    }
+
+    #[test]
+    fn underline_empty_span_at_end_marks_one_column() {
+        let span = Span::new(8, 8);
+
+        let actual = span.underline("10 PRINT");
+
+        assert_eq!("10 PRINT\n        ^", actual);
+    }
+}
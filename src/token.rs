@@ -1,6 +1,11 @@
+use std::fmt;
+
+use crate::ast::Function;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     Comma,
+    Colon,
     OpeningParenthesis,
     ClosingParenthesis,
     Equal,
@@ -13,19 +18,117 @@ pub enum Token {
     Minus,
     Multiply,
     Divide,
+    IntegerDivide,
+    Power,
+    Mod,
     NumberLiteral(i16),
-    StringLiteral { value: Vec<u8> },
-    Variable { identifier: u8 },
+    /// Number with a fraction or an exponent, like `1.5` or `2E-3`.
+    FloatLiteral(f64),
+    StringLiteral {
+        value: Vec<u8>,
+    },
+    /// Variable like `A` or `TOTAL2`, with its name in upper case.
+    Variable {
+        name: String,
+    },
+    /// Name of a built-in function.
+    Function(Function),
+    /// Variable holding a string, like `A$`, with its name in upper case and without the `$`.
+    StringVariable {
+        name: String,
+    },
     Print,
     If,
     Then,
+    Else,
+    And,
+    Or,
+    Not,
     Goto,
     Input,
     Let,
+    Dim,
     GoSub,
     Return,
     Clear,
     List,
     Run,
     End,
+    Bye,
+    For,
+    To,
+    Step,
+    Next,
+    While,
+    Wend,
+    Do,
+    Loop,
+    Until,
+    /// `REM` and the rest of the line after it
+    Remark {
+        text: Vec<u8>,
+    },
+    /// `'` and the rest of the line after it
+    Comment {
+        text: Vec<u8>,
+    },
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Comma => write!(f, ","),
+            Token::Colon => write!(f, ":"),
+            Token::OpeningParenthesis => write!(f, "("),
+            Token::ClosingParenthesis => write!(f, ")"),
+            Token::Equal => write!(f, "="),
+            Token::NotEqual => write!(f, "<>"),
+            Token::LessThan => write!(f, "<"),
+            Token::LessThanOrEqual => write!(f, "<="),
+            Token::GreaterThan => write!(f, ">"),
+            Token::GreaterThanOrEqual => write!(f, ">="),
+            Token::Plus => write!(f, "+"),
+            Token::Minus => write!(f, "-"),
+            Token::Multiply => write!(f, "*"),
+            Token::Divide => write!(f, "/"),
+            Token::IntegerDivide => write!(f, "\\"),
+            Token::Power => write!(f, "^"),
+            Token::Mod => write!(f, "MOD"),
+            Token::NumberLiteral(value) => write!(f, "{}", value),
+            Token::FloatLiteral(value) => write!(f, "{}", value),
+            Token::StringLiteral { value } => write!(f, "\"{}\"", String::from_utf8_lossy(value)),
+            Token::Variable { name } => write!(f, "{}", name),
+            Token::StringVariable { name } => write!(f, "{}$", name),
+            Token::Function(function) => write!(f, "{}", function),
+            Token::Print => write!(f, "PRINT"),
+            Token::If => write!(f, "IF"),
+            Token::Then => write!(f, "THEN"),
+            Token::Else => write!(f, "ELSE"),
+            Token::And => write!(f, "AND"),
+            Token::Or => write!(f, "OR"),
+            Token::Not => write!(f, "NOT"),
+            Token::Goto => write!(f, "GOTO"),
+            Token::Input => write!(f, "INPUT"),
+            Token::Let => write!(f, "LET"),
+            Token::Dim => write!(f, "DIM"),
+            Token::GoSub => write!(f, "GOSUB"),
+            Token::Return => write!(f, "RETURN"),
+            Token::Clear => write!(f, "CLEAR"),
+            Token::List => write!(f, "LIST"),
+            Token::Run => write!(f, "RUN"),
+            Token::End => write!(f, "END"),
+            Token::Bye => write!(f, "BYE"),
+            Token::For => write!(f, "FOR"),
+            Token::To => write!(f, "TO"),
+            Token::Step => write!(f, "STEP"),
+            Token::Next => write!(f, "NEXT"),
+            Token::While => write!(f, "WHILE"),
+            Token::Wend => write!(f, "WEND"),
+            Token::Do => write!(f, "DO"),
+            Token::Loop => write!(f, "LOOP"),
+            Token::Until => write!(f, "UNTIL"),
+            Token::Remark { .. } => write!(f, "REM"),
+            Token::Comment { .. } => write!(f, "'"),
+        }
+    }
 }